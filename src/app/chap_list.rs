@@ -1,20 +1,117 @@
-use itertools::Either;
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Color, Style},
-    text::Line,
+    style::Style,
+    text::{Line, Span},
     widgets::StatefulWidget,
 };
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::iter::zip;
-use std::marker::PhantomData;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use royal_api::{ChapterReference, Fiction};
+use chrono::{Local, TimeZone};
+use royal_api::{ChapterReference, Fiction, FictionStatus};
+
+use super::config::{ChapterColumn, ChapterListConfig};
+use super::history::History;
+use super::shelves::Shelves;
+use super::text;
+use super::theme::Theme;
 
 pub trait Listable: Debug {
-    fn to_string(&self, width: u16, x_margin: u16) -> String;
+    /// whatever rows need besides the item itself, like the reading history
+    type Context<'a>;
+    /// what the filter matches against
+    fn title(&self) -> &str;
+
+    /// the row for the item at `index` of `ListState::items`
+    fn to_line(
+        &self,
+        index: usize,
+        width: u16,
+        x_margin: u16,
+        context: &Self::Context<'_>,
+        highlight: &Highlight<'_>,
+    ) -> Line<'static>;
+
+    /// the header row of `group`, with how many items it holds
+    fn group_line(
+        group: &Group,
+        _items: &[Self],
+        width: u16,
+        x_margin: u16,
+        _context: &Self::Context<'_>,
+    ) -> Line<'static>
+    where
+        Self: Sized,
+    {
+        let width = width.saturating_sub(2 * x_margin) as usize;
+        let header = format!(
+            "{} {} ({})",
+            group.marker(),
+            group.title,
+            group.members.len()
+        );
+        Line::raw(text::truncate(&header, width, "…"))
+    }
+
+    /// lines per item, the same for every item of a list
+    fn row_height(_context: &Self::Context<'_>) -> u16 {
+        1
+    }
+
+    /// the lines drawn under `to_line` when `row_height` is more than 1
+    fn details(
+        &self,
+        _index: usize,
+        _width: u16,
+        _x_margin: u16,
+        _context: &Self::Context<'_>,
+    ) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The characters of a title matched by the filter, and the style they are drawn in.
+pub struct Highlight<'a> {
+    /// char indices into the title
+    pub positions: &'a [usize],
+    pub style: Style,
+}
+
+impl Highlight<'_> {
+    /// `title` cut to `max` columns, split so the matched characters stand out
+    fn title(&self, title: &str, max: usize) -> Vec<Span<'static>> {
+        let cut = text::truncate(title, max, "…");
+        // the ellipsis is not part of the title, whatever its position
+        let kept = zip(cut.chars(), title.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        let mut run = String::new();
+        let mut matched = false;
+        for (i, c) in cut.chars().enumerate() {
+            let is_match = i < kept && self.positions.contains(&i);
+            if is_match != matched && !run.is_empty() {
+                spans.push(self.span(std::mem::take(&mut run), matched));
+            }
+            matched = is_match;
+            run.push(c);
+        }
+        if !run.is_empty() {
+            spans.push(self.span(run, matched));
+        }
+        spans
+    }
+
+    fn span(&self, text: String, matched: bool) -> Span<'static> {
+        if matched {
+            Span::styled(text, self.style)
+        } else {
+            Span::raw(text)
+        }
+    }
 }
 
 const MINUTE: u64 = 60;
@@ -24,119 +121,931 @@ const WEEK: u64 = 7 * DAY;
 const MONTH: u64 = 30 * DAY;
 const YEAR: u64 = 365 * DAY;
 
-impl Listable for ChapterReference {
-    fn to_string(&self, width: u16, x_margin: u16) -> String {
-        let width = width - x_margin * 2;
-        let s = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as u64
-            - self.time;
-        let time = match s {
-            YEAR.. => format!("{} years", s / YEAR),
-            MONTH.. => format!("{} months", s / MONTH),
-            WEEK.. => format!("{} weeks", s / WEEK),
-            DAY.. => format!("{} days", s / DAY),
-            HOUR.. => format!("{} hours", s / HOUR),
-            MINUTE.. => format!("{} minutes", s / MINUTE),
-            _ => format!("{} seconds", s),
+/// narrowest the title column gets before other columns are dropped
+const MIN_TITLE_WIDTH: usize = 12;
+/// columns dropped first when the sidebar is narrow
+const DROP_ORDER: &[ChapterColumn] = &[
+    ChapterColumn::Date,
+    ChapterColumn::ReadingTime,
+    ChapterColumn::Words,
+    ChapterColumn::Number,
+    ChapterColumn::Age,
+    ChapterColumn::Icons,
+];
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// compact time since `time`, at most 4 columns; clock skew reads as `now`
+pub fn age(time: u64, now: u64) -> String {
+    let s = now.saturating_sub(time);
+    match s {
+        YEAR.. => format!("{}y", s / YEAR),
+        MONTH.. => format!("{}mo", s / MONTH),
+        WEEK.. => format!("{}w", s / WEEK),
+        DAY.. => format!("{}d", s / DAY),
+        HOUR.. => format!("{}h", s / HOUR),
+        MINUTE.. => format!("{}m", s / MINUTE),
+        _ => String::from("now"),
+    }
+}
+
+/// What chapter rows are drawn from.
+pub struct ChapterContext<'a> {
+    pub history: &'a History,
+    pub config: &'a ChapterListConfig,
+    /// chapters released after this and not read yet are new
+    pub new_since: Option<u64>,
+    pub now: u64,
+    /// number of chapters, for the width of the number column
+    pub total: usize,
+}
+
+impl ChapterContext<'_> {
+    fn column_width(&self, column: ChapterColumn) -> usize {
+        match column {
+            ChapterColumn::Number => self.total.max(1).ilog10() as usize + 1,
+            ChapterColumn::Title => 0,
+            ChapterColumn::Date => 10,
+            ChapterColumn::Age => 4,
+            ChapterColumn::Words => 5,
+            ChapterColumn::ReadingTime => 6,
+            ChapterColumn::Icons => 2,
+        }
+    }
+
+    /// the configured columns that fit in `width`, dropping the least useful first
+    fn columns(&self, width: usize) -> Vec<ChapterColumn> {
+        let mut columns = self.config.columns.clone();
+        let min_title = if columns.contains(&ChapterColumn::Title) {
+            MIN_TITLE_WIDTH
+        } else {
+            0
         };
-        let full_len = self.title.len() + time.len();
-        let spacing_width = (width as i32 - full_len as i32).max(3);
-        let spacing = String::from_utf8(vec![b' '; spacing_width as usize]).unwrap();
-        let name = self
-            .title
-            .chars()
-            .take(width as usize - time.len() - spacing_width as usize)
-            .collect::<String>();
-        format!("{}{}{}", name, spacing, time)
+        for drop in DROP_ORDER {
+            let fixed = columns
+                .iter()
+                .filter(|c| **c != ChapterColumn::Title)
+                .map(|c| self.column_width(*c) + 1)
+                .sum::<usize>();
+            if fixed + min_title <= width {
+                break;
+            }
+            columns.retain(|c| c != drop);
+        }
+        columns
+    }
+}
+
+impl Listable for ChapterReference {
+    type Context<'a> = ChapterContext<'a>;
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// the volume title and how many of its chapters were read
+    fn group_line(
+        group: &Group,
+        items: &[ChapterReference],
+        width: u16,
+        x_margin: u16,
+        context: &ChapterContext<'_>,
+    ) -> Line<'static> {
+        let width = width.saturating_sub(x_margin * 2) as usize;
+        let read = group
+            .members
+            .iter()
+            .filter_map(|i| items.get(*i))
+            .filter(|c| context.history.is_read(&c.path))
+            .count();
+        let progress = format!("{}/{}", read, group.members.len());
+        let title_width = width.saturating_sub(2 + progress.len() + 1);
+        let title = text::truncate(&group.title, title_width, "…");
+        let padding = title_width.saturating_sub(text::width(&title));
+        Line::raw(format!(
+            "{} {}{} {}",
+            group.marker(),
+            title,
+            " ".repeat(padding),
+            progress
+        ))
+    }
+
+    fn to_line(
+        &self,
+        index: usize,
+        width: u16,
+        x_margin: u16,
+        context: &ChapterContext<'_>,
+        highlight: &Highlight<'_>,
+    ) -> Line<'static> {
+        let width = width.saturating_sub(x_margin * 2) as usize;
+        let columns = context.columns(width);
+        let info = context.history.info(&self.path);
+        let fixed = columns
+            .iter()
+            .filter(|c| **c != ChapterColumn::Title)
+            .map(|c| context.column_width(*c) + 1)
+            .sum::<usize>();
+        let title_width = width.saturating_sub(fixed);
+        let mut spans = Vec::new();
+        for column in &columns {
+            if !spans.is_empty() {
+                spans.push(Span::raw(" "));
+            }
+            let cell_width = context.column_width(*column);
+            let cell = match column {
+                ChapterColumn::Number => format!("{:>1$}", index + 1, cell_width),
+                ChapterColumn::Title => {
+                    let title = highlight.title(&self.title, title_width);
+                    let used = title.iter().map(|s| s.width()).sum::<usize>();
+                    spans.extend(title);
+                    spans.push(Span::raw(" ".repeat(title_width.saturating_sub(used))));
+                    continue;
+                }
+                ChapterColumn::Date => Local
+                    .timestamp_opt(self.time as i64, 0)
+                    .single()
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+                ChapterColumn::Age => age(self.time, context.now),
+                ChapterColumn::Words => info.map(|i| words(i.words)).unwrap_or_default(),
+                ChapterColumn::ReadingTime => info
+                    .map(|i| {
+                        let minutes = i.words.div_ceil(context.config.words_per_minute.max(1));
+                        format!("{} min", minutes)
+                    })
+                    .unwrap_or_default(),
+                ChapterColumn::Icons => {
+                    let state = if self.locked {
+                        '⊘'
+                    } else if context.history.is_read(&self.path) {
+                        '✓'
+                    } else if context.new_since.is_some_and(|since| self.time > since) {
+                        '•'
+                    } else {
+                        ' '
+                    };
+                    let edited = if info.is_some_and(|i| i.is_edited()) {
+                        '✎'
+                    } else {
+                        ' '
+                    };
+                    format!("{}{}", state, edited)
+                }
+            };
+            spans.push(Span::raw(format!("{:>1$}", cell, cell_width)));
+        }
+        Line::from(spans)
+    }
+}
+
+/// Groups the chapters of `fiction` by volume, or not at all if it has fewer than two.
+/// Chapters of no known volume get a group of their own. Volumes read to the end start
+/// collapsed, so the list opens on what is left.
+pub fn volume_groups(fiction: &Fiction, history: &History) -> Vec<Group> {
+    let mut groups = fiction
+        .volumes
+        .iter()
+        .map(|volume| Group {
+            title: volume.title.clone(),
+            members: Vec::new(),
+            collapsed: false,
+        })
+        .collect::<Vec<_>>();
+    let mut other = Vec::new();
+    for (index, chapter) in fiction.chapters.iter().enumerate() {
+        match fiction
+            .volumes
+            .iter()
+            .position(|v| Some(v.id) == chapter.volume)
+        {
+            Some(v) => groups[v].members.push(index),
+            None => other.push(index),
+        }
+    }
+    if !other.is_empty() {
+        groups.push(Group {
+            title: String::from("Other chapters"),
+            members: other,
+            collapsed: false,
+        });
+    }
+    groups.retain(|g| !g.members.is_empty());
+    if groups.len() < 2 {
+        return Vec::new();
+    }
+    for group in &mut groups {
+        group.collapsed = group
+            .members
+            .iter()
+            .all(|i| history.is_read(&fiction.chapters[*i].path));
+    }
+    groups
+}
+
+/// word count like `850` or `12.3k`
+fn words(count: usize) -> String {
+    if count < 1000 {
+        count.to_string()
+    } else if count < 99_950 {
+        format!("{:.1}k", count as f64 / 1000.0)
+    } else {
+        // from 99,950 one decimal would round up to `100.0k`, too wide for the column
+        format!("{}k", (count + 500) / 1000)
+    }
+}
+
+/// What fiction rows are drawn from.
+pub struct FictionContext<'a> {
+    pub history: &'a History,
+    pub shelves: &'a Shelves,
+    /// a second line with the author, age, status, shelf and tags
+    pub expanded: bool,
+    pub now: u64,
+}
+
+/// unread chapters of `fiction`, locked ones included since they will unlock
+pub fn unread(fiction: &Fiction, history: &History) -> usize {
+    fiction
+        .chapters
+        .iter()
+        .filter(|c| !history.is_read(&c.path))
+        .count()
+}
+
+/// release time of the newest chapter
+pub fn last_update(fiction: &Fiction) -> Option<u64> {
+    fiction.chapters.iter().map(|c| c.time).max()
+}
+
+fn status_icon(status: FictionStatus) -> char {
+    match status {
+        FictionStatus::Unknown => ' ',
+        FictionStatus::Ongoing => '▸',
+        FictionStatus::Completed => '■',
+        FictionStatus::Hiatus => '‖',
+        FictionStatus::Stub => '·',
+        FictionStatus::Dropped => '×',
     }
 }
 
 impl Listable for Fiction {
-    fn to_string(&self, width: u16, x_margin: u16) -> String {
-        if self.title.len() as u16 > width - 2 - 2 * x_margin {
-            format!(
-                "{}...",
-                self.title
-                    .chars()
-                    .take((width - 5 - 2 * x_margin) as usize)
-                    .collect::<String>()
-            )
+    type Context<'a> = FictionContext<'a>;
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// status, title and unread count, plus the age of the last chapter when compact
+    fn to_line(
+        &self,
+        _: usize,
+        width: u16,
+        x_margin: u16,
+        context: &FictionContext<'_>,
+        highlight: &Highlight<'_>,
+    ) -> Line<'static> {
+        let width = width.saturating_sub(2 * x_margin) as usize;
+        let unread = match unread(self, context.history) {
+            0 => String::new(),
+            n => n.to_string(),
+        };
+        let age = match last_update(self) {
+            Some(time) if !context.expanded => age(time, context.now),
+            _ => String::new(),
+        };
+        let right = format!("{:>4} {:>4}", unread, age);
+        let right = right.trim_end();
+        let title_width = width.saturating_sub(2 + text::width(right) + 1);
+        let mut spans = vec![Span::raw(format!("{} ", status_icon(self.status)))];
+        if title_width < MIN_TITLE_WIDTH {
+            spans.extend(highlight.title(&self.title, width.saturating_sub(2)));
+            return Line::from(spans);
+        }
+        let title = highlight.title(&self.title, title_width);
+        let padding = title_width - title.iter().map(|s| s.width()).sum::<usize>();
+        spans.extend(title);
+        spans.push(Span::raw(format!("{} {}", " ".repeat(padding), right)));
+        Line::from(spans)
+    }
+
+    fn row_height(context: &FictionContext<'_>) -> u16 {
+        if context.expanded {
+            2
         } else {
-            self.title.clone()
+            1
         }
     }
+
+    fn details(
+        &self,
+        _: usize,
+        width: u16,
+        x_margin: u16,
+        context: &FictionContext<'_>,
+    ) -> Vec<String> {
+        let width = width.saturating_sub(2 * x_margin) as usize;
+        let mut parts = Vec::new();
+        if !self.author.is_empty() {
+            parts.push(format!("by {}", self.author));
+        }
+        if let Some(time) = last_update(self) {
+            parts.push(format!("{} ago", age(time, context.now)));
+        }
+        if self.status != FictionStatus::Unknown {
+            parts.push(self.status.name().to_string());
+        }
+        let shelves = context.shelves;
+        parts.push(shelves.names[shelves.shelf(self.id)].clone());
+        let tags = shelves.tags(self.id);
+        if !tags.is_empty() {
+            parts.push(
+                tags.iter()
+                    .map(|t| format!("#{}", t))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        }
+        let line = format!("  {}", parts.join(" · "));
+        vec![text::truncate(&line, width, "…")]
+    }
 }
 
-pub struct ListWidget<T: Listable> {
-    phantom: PhantomData<T>,
+pub struct ListWidget<'a, T: Listable> {
     margin: (u16, u16),
+    theme: Theme,
+    context: &'a T::Context<'a>,
+}
+
+/// A collapsible header over some of the items, like a volume over its chapters.
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub title: String,
+    /// indices into `ListState::items`
+    pub members: Vec<usize>,
+    pub collapsed: bool,
+}
+
+impl Group {
+    /// the arrow in front of the header
+    fn marker(&self) -> char {
+        if self.collapsed {
+            '▸'
+        } else {
+            '▾'
+        }
+    }
+}
+
+/// What a row of the list shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    /// index into `ListState::groups`
+    Group(usize),
+    /// index into `ListState::items`
+    Item(usize),
 }
 
 #[derive(Debug)]
 pub struct ListState<T: Listable> {
+    /// row of the selection, an index into `order`
     pub selected_line: u16,
     pub top_line: u16,
     pub items: Vec<T>,
     pub reversed: bool,
+    /// what each row shows
+    order: Vec<Row>,
+    /// every item in display order, `order` is what the filter and groups make of it
+    sorted: Vec<usize>,
+    filter: String,
+    /// char indices of each shown item's title that matched the filter
+    matches: HashMap<usize, Vec<usize>>,
+    /// items that match the filter, collapsed or not
+    shown: usize,
+    groups: Vec<Group>,
+    /// index into `groups` of each item's group, if it has one
+    group_of: Vec<Option<usize>>,
+    /// words a filter starting with `#` matches instead of the title, by item
+    tags: Vec<Vec<String>>,
 }
 
 impl<T: Listable> ListState<T> {
     pub fn new(items: Vec<T>, selected_line: u16, top_line: u16) -> ListState<T> {
         Self {
+            order: (0..items.len()).map(Row::Item).collect(),
+            sorted: (0..items.len()).collect(),
+            shown: items.len(),
+            group_of: vec![None; items.len()],
             items,
             selected_line,
             top_line,
-            reversed: true,
+            reversed: false,
+            filter: String::new(),
+            matches: HashMap::new(),
+            groups: Vec::new(),
+            tags: Vec::new(),
         }
     }
+
+    /// Replaces the items, shown in their own order until `set_order` is called.
+    /// The filter and groups are cleared, they were meant for the old items.
+    pub fn set_items(&mut self, items: Vec<T>) {
+        self.sorted = (0..items.len()).collect();
+        self.order = self.sorted.iter().map(|i| Row::Item(*i)).collect();
+        self.shown = items.len();
+        self.group_of = vec![None; items.len()];
+        self.items = items;
+        self.filter.clear();
+        self.matches.clear();
+        self.groups.clear();
+        self.tags.clear();
+        self.selected_line = 0;
+        self.top_line = 0;
+    }
+
+    /// Shows the items in `sorted` order, upside down if `reversed`.
+    /// The selection stays on the same item rather than the same row.
+    pub fn set_order(&mut self, mut sorted: Vec<usize>) {
+        if self.reversed {
+            sorted.reverse();
+        }
+        self.sorted = sorted;
+        self.apply_filter();
+    }
+
+    /// Like `set_order`, but if the selected item is no longer shown the selection stays on
+    /// the same row, on whatever came after it. For an item moved out of the list, like a
+    /// fiction moved to another shelf.
+    pub fn set_order_keeping_row(&mut self, sorted: Vec<usize>) {
+        let (line, top) = (self.selected_line, self.top_line);
+        let row = self.order.get(line as usize).copied();
+        self.set_order(sorted);
+        if row.is_some_and(|row| self.line_of(row).is_none()) {
+            self.selected_line = line.min(self.order.len().saturating_sub(1) as u16);
+            self.top_line = top;
+        }
+    }
+
+    /// Sets the tags of each item, for filters like `#tag`. They are matched from the
+    /// next `set_order` on.
+    pub fn set_tags(&mut self, tags: Vec<Vec<String>>) {
+        self.tags = tags;
+    }
+
+    pub fn reverse(&mut self) {
+        self.reversed = !self.reversed;
+        self.sorted.reverse();
+        self.apply_filter();
+    }
+
+    /// Puts items under group headers. A group is shown where its first item would be
+    /// in the current order, with all of its items after it. Items in no group stay
+    /// where they are.
+    pub fn set_groups(&mut self, groups: Vec<Group>) {
+        self.group_of = vec![None; self.items.len()];
+        for (g, group) in groups.iter().enumerate() {
+            for index in &group.members {
+                if let Some(slot) = self.group_of.get_mut(*index) {
+                    *slot = Some(g);
+                }
+            }
+        }
+        self.groups = groups;
+        self.apply_filter();
+    }
+
+    /// Collapses or expands the group whose header is selected.
+    /// Returns false if an item is selected instead.
+    pub fn toggle_selected_group(&mut self) -> bool {
+        let Some(Row::Group(g)) = self.order.get(self.selected_line as usize).copied() else {
+            return false;
+        };
+        self.groups[g].collapsed = !self.groups[g].collapsed;
+        self.apply_filter();
+        true
+    }
+
+    /// Collapses every group, or expands them all if they already are.
+    pub fn toggle_all_groups(&mut self) {
+        let collapse = self.groups.iter().any(|g| !g.collapsed);
+        for group in &mut self.groups {
+            group.collapsed = collapse;
+        }
+        self.apply_filter();
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Shows only the items whose title fuzzy matches `filter`, all of them if it is empty.
+    /// In a list with tags, `#tag` shows the items with a tag matching `tag` instead.
+    /// The selection stays on the same item if it is still shown, else goes to the top.
+    pub fn set_filter(&mut self, filter: &str) {
+        if filter != self.filter {
+            self.filter = filter.to_string();
+            self.apply_filter();
+        }
+    }
+
+    /// number of items the filter lets through
+    pub fn shown(&self) -> usize {
+        self.shown
+    }
+
+    /// Rebuilds the rows from the sorted items, the filter and the groups.
+    /// Groups are all open while filtering, so every match can be seen.
+    fn apply_filter(&mut self) {
+        let selected = self.order.get(self.selected_line as usize).copied();
+        self.matches.clear();
+        let filtering = !self.filter.trim().is_empty();
+        let mut shown = Vec::new();
+        for index in &self.sorted {
+            let Some(positions) = self.passes_filter(*index) else {
+                continue;
+            };
+            if !positions.is_empty() {
+                self.matches.insert(*index, positions);
+            }
+            shown.push(*index);
+        }
+        self.shown = shown.len();
+        let mut members = vec![Vec::new(); self.groups.len()];
+        for index in &shown {
+            if let Some(g) = self.group_of.get(*index).copied().flatten() {
+                members[g].push(*index);
+            }
+        }
+        self.order = Vec::with_capacity(shown.len() + self.groups.len());
+        for index in shown {
+            match self.group_of.get(index).copied().flatten() {
+                None => self.order.push(Row::Item(index)),
+                // the rest of the group was added with its first item
+                Some(g) if members[g].first() != Some(&index) => {}
+                Some(g) => {
+                    self.order.push(Row::Group(g));
+                    if filtering || !self.groups[g].collapsed {
+                        self.order.extend(members[g].iter().map(|i| Row::Item(*i)));
+                    }
+                }
+            }
+        }
+        match selected.and_then(|row| self.line_of(row)) {
+            Some(line) => self.selected_line = line as u16,
+            None => {
+                self.selected_line = 0;
+                self.top_line = 0;
+            }
+        }
+    }
+
+    /// None if `items[index]` is filtered out, else the char indices of its title that
+    /// matched, empty when the filter is empty or a `#tag`
+    fn passes_filter(&self, index: usize) -> Option<Vec<usize>> {
+        let item = self.items.get(index)?;
+        let filter = self.filter.trim();
+        if filter.is_empty() {
+            return Some(Vec::new());
+        }
+        match filter.strip_prefix('#').filter(|_| !self.tags.is_empty()) {
+            Some(tag) => {
+                let tags = self.tags.get(index).map_or(&[][..], Vec::as_slice);
+                tags.iter()
+                    .any(|t| text::fuzzy_match(tag, t).is_some())
+                    .then(Vec::new)
+            }
+            None => text::fuzzy_match(&self.filter, item.title()),
+        }
+    }
+
+    /// the line showing `row`; a shown item folded into a collapsed group is on the
+    /// group's header
+    fn line_of(&self, row: Row) -> Option<usize> {
+        self.order
+            .iter()
+            .position(|r| *r == row)
+            .or_else(|| match row {
+                Row::Item(index) => {
+                    let g = self.group_of.get(index).copied().flatten()?;
+                    let folded = self.groups[g].collapsed
+                        && self.sorted.contains(&index)
+                        && self.passes_filter(index).is_some();
+                    if !folded {
+                        return None;
+                    }
+                    self.order.iter().position(|r| *r == Row::Group(g))
+                }
+                Row::Group(_) => None,
+            })
+    }
+
+    /// index into `items` of the selected item, none when a group header is selected
+    pub fn selected(&self) -> Option<usize> {
+        match self.order.get(self.selected_line as usize)? {
+            Row::Item(index) => Some(*index).filter(|i| *i < self.items.len()),
+            Row::Group(_) => None,
+        }
+    }
+
+    pub fn selected_item(&self) -> Option<&T> {
+        self.items.get(self.selected()?)
+    }
+
+    /// moves the selection to `items[index]`, opening its group if it is collapsed
+    pub fn select(&mut self, index: usize) {
+        if let Some(g) = self.group_of.get(index).copied().flatten() {
+            if self.groups[g].collapsed {
+                self.groups[g].collapsed = false;
+                self.apply_filter();
+            }
+        }
+        if let Some(line) = self.order.iter().position(|r| *r == Row::Item(index)) {
+            self.selected_line = line as u16;
+        }
+    }
+
+    /// Scrolls a `ListWidget` with `margin` drawn in `area` so the selection is in the
+    /// middle, or as close as the ends of the list allow.
+    pub fn center_selected(&mut self, area: Rect, margin: (u16, u16), row_height: u16) {
+        let rows = area.height.saturating_sub(2 + margin.1 * 2) / row_height.max(1);
+        let last_top = (self.order.len() as u16).saturating_sub(rows);
+        self.top_line = self.selected_line.saturating_sub(rows / 2).min(last_top);
+    }
+
+    /// the line a `ListWidget` with `margin` drawn in `area` shows at terminal row `row`
+    pub fn line_at(
+        &self,
+        area: Rect,
+        margin: (u16, u16),
+        row_height: u16,
+        row: u16,
+    ) -> Option<u16> {
+        let first = area.y + margin.1 + 1;
+        let row_height = row_height.max(1);
+        let height = area.height.saturating_sub(2 + margin.1 * 2) / row_height * row_height;
+        if row < first || row >= first + height {
+            return None;
+        }
+        let line = self.top_line + (row - first) / row_height;
+        ((line as usize) < self.order.len()).then_some(line)
+    }
 }
 
-impl<T: Listable> ListWidget<T> {
-    pub fn new(margin: (u16, u16)) -> ListWidget<T> {
+impl<'a, T: Listable> ListWidget<'a, T> {
+    pub fn new(margin: (u16, u16), theme: Theme, context: &'a T::Context<'a>) -> ListWidget<'a, T> {
         Self {
-            phantom: PhantomData,
             margin,
+            theme,
+            context,
         }
     }
 }
 
-impl<T: Listable> StatefulWidget for ListWidget<T> {
+impl<T: Listable> StatefulWidget for ListWidget<'_, T> {
     type State = ListState<T>;
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let row_height = T::row_height(self.context).max(1);
+        // in items, not terminal rows
+        let height = area.height.saturating_sub(2 + self.margin.1 * 2) / row_height;
+        let width = area.width.saturating_sub(2 + self.margin.0 * 2);
         // state not validated in event handler
-        if state.items.is_empty() {
+        if state.order.is_empty() || height == 0 || width == 0 {
             return;
         }
-        state.selected_line = state.selected_line.clamp(0, state.items.len() as u16 - 1);
+        state.selected_line = state.selected_line.clamp(0, state.order.len() as u16 - 1);
         state.top_line = state.top_line.clamp(
-            (state.selected_line as i32 - (area.height as i32 - 3 - self.margin.1 as i32 * 2))
-                .max(0) as u16,
+            (state.selected_line + 1).saturating_sub(height),
             state.selected_line,
         );
-        let num_entries =
-            (area.height - 2 - self.margin.1 * 2).min(state.items.len() as u16 - state.top_line);
+        let num_entries = height.min(state.order.len() as u16 - state.top_line);
         let line_num = (state.top_line..).take(num_entries as usize);
-        let item_iter = if state.reversed {
-            Either::Left(state.items.iter().rev())
-        } else {
-            Either::Right(state.items.iter())
-        }
-        .skip(state.top_line as usize)
-        .take(num_entries as usize);
-        for (i, item) in zip(line_num, item_iter) {
-            let style = if i as u16 == state.selected_line {
-                Style::default().fg(Color::Black).bg(Color::Blue)
+        let rows = state
+            .order
+            .iter()
+            .skip(state.top_line as usize)
+            .take(num_entries as usize);
+        for (i, row) in zip(line_num, rows) {
+            let selected = i == state.selected_line;
+            let y = area.y + self.margin.1 + 1 + (i - state.top_line) * row_height;
+            let (index, item) = match *row {
+                Row::Group(g) => {
+                    let style = if selected {
+                        self.theme.selection
+                    } else {
+                        self.theme.border
+                    };
+                    let line = T::group_line(
+                        &state.groups[g],
+                        &state.items,
+                        area.width,
+                        self.margin.0 + 1,
+                        self.context,
+                    );
+                    buf.set_line(area.x + 1 + self.margin.0, y, &line.style(style), width);
+                    continue;
+                }
+                Row::Item(index) => match state.items.get(index) {
+                    Some(item) => (index, item),
+                    None => continue,
+                },
+            };
+            let style = if selected {
+                self.theme.selection
             } else {
-                Style::default().fg(Color::White)
+                self.theme.text
             };
-            buf.set_line(
-                area.x + 1 + self.margin.0,
-                area.y + self.margin.1 + 1 + i - state.top_line,
-                &Line::styled(item.to_string(area.width, self.margin.0 + 1), style),
+            let highlight = Highlight {
+                positions: state.matches.get(&index).map_or(&[], Vec::as_slice),
+                style: self.theme.highlight,
+            };
+            let line = item.to_line(
+                index,
                 area.width,
+                self.margin.0 + 1,
+                self.context,
+                &highlight,
             );
+            buf.set_line(area.x + 1 + self.margin.0, y, &line.style(style), width);
+            let details = item.details(index, area.width, self.margin.0 + 1, self.context);
+            for (k, detail) in details.iter().take(row_height as usize - 1).enumerate() {
+                let style = if selected { style } else { self.theme.dim };
+                buf.set_line(
+                    area.x + 1 + self.margin.0,
+                    y + 1 + k as u16,
+                    &Line::styled(detail.as_str(), style),
+                    width,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use royal_api::Volume;
+
+    fn chapter(title: &str, volume: Option<usize>) -> ChapterReference {
+        ChapterReference {
+            path: format!("/chapter/{}", title.to_lowercase()),
+            title: title.to_string(),
+            volume,
+            ..Default::default()
+        }
+    }
+
+    /// two volumes, the second read to the end, and a chapter of neither
+    fn fiction() -> Fiction {
+        Fiction {
+            title: String::from("Fiction"),
+            id: 1,
+            author: String::new(),
+            description: String::new(),
+            status: FictionStatus::Ongoing,
+            chapters: vec![
+                chapter("Magic", Some(10)),
+                chapter("Beta", Some(10)),
+                chapter("Gamma", Some(20)),
+                chapter("Delta", Some(20)),
+                chapter("Epsilon", None),
+            ],
+            volumes: vec![
+                Volume {
+                    id: 10,
+                    title: String::from("Book One"),
+                },
+                Volume {
+                    id: 20,
+                    title: String::from("Book Two"),
+                },
+            ],
+        }
+    }
+
+    fn history() -> History {
+        let mut history = History::default();
+        history.mark_read("/chapter/gamma");
+        history.mark_read("/chapter/delta");
+        history
+    }
+
+    fn list() -> ListState<ChapterReference> {
+        let fiction = fiction();
+        let groups = volume_groups(&fiction, &history());
+        let mut list = ListState::new(fiction.chapters, 0, 0);
+        list.set_groups(groups);
+        list
+    }
+
+    #[test]
+    fn word_counts_fit_five_columns() {
+        assert_eq!(words(999), "999");
+        assert_eq!(words(12_345), "12.3k");
+        assert_eq!(words(99_949), "99.9k");
+        assert_eq!(words(99_950), "100k");
+        assert_eq!(words(99_999), "100k");
+        assert_eq!(words(123_456), "123k");
+        for count in [1000, 9_999, 99_950, 999_999, 9_999_000] {
+            assert!(words(count).len() <= 5, "{}", words(count));
         }
     }
+
+    #[test]
+    fn volume_groups_collapse_volumes_read_to_the_end() {
+        let groups = volume_groups(&fiction(), &history());
+        let summary = groups
+            .iter()
+            .map(|g| (g.title.as_str(), g.members.clone(), g.collapsed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("Book One", vec![0, 1], false),
+                ("Book Two", vec![2, 3], true),
+                ("Other chapters", vec![4], false),
+            ]
+        );
+    }
+
+    #[test]
+    fn volume_groups_need_two_volumes() {
+        let mut fiction = fiction();
+        fiction.chapters.truncate(2);
+        assert!(volume_groups(&fiction, &History::default()).is_empty());
+        fiction.volumes.clear();
+        assert!(volume_groups(&fiction, &History::default()).is_empty());
+    }
+
+    #[test]
+    fn groups_follow_the_order_of_their_first_item() {
+        let mut list = list();
+        assert_eq!(
+            list.order,
+            [
+                Row::Group(0),
+                Row::Item(0),
+                Row::Item(1),
+                Row::Group(1),
+                Row::Group(2),
+                Row::Item(4),
+            ]
+        );
+        list.set_order(vec![4, 3, 2, 1, 0]);
+        assert_eq!(
+            list.order,
+            [
+                Row::Group(2),
+                Row::Item(4),
+                Row::Group(1),
+                Row::Group(0),
+                Row::Item(1),
+                Row::Item(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn collapsing_a_group_moves_the_selection_to_its_header() {
+        let mut list = list();
+        list.select(3);
+        assert_eq!(list.selected(), Some(3));
+        list.toggle_all_groups();
+        assert_eq!(list.order, [Row::Group(0), Row::Group(1), Row::Group(2)]);
+        assert_eq!(list.selected_line, 1);
+        assert!(list.toggle_selected_group());
+        assert_eq!(list.order[2..], [Row::Item(2), Row::Item(3), Row::Group(2)]);
+    }
+
+    #[test]
+    fn filtering_opens_collapsed_groups_and_drops_empty_ones() {
+        let mut list = list();
+        list.set_filter("ta");
+        assert_eq!(
+            list.order,
+            [Row::Group(0), Row::Item(1), Row::Group(1), Row::Item(3)]
+        );
+        assert_eq!(list.shown, 2);
+        list.set_filter("");
+        assert_eq!(list.order.len(), 6);
+    }
+
+    #[test]
+    fn a_filtered_out_item_does_not_select_its_group() {
+        let mut list = list();
+        list.set_filter("a");
+        list.selected_line = 5;
+        list.top_line = 2;
+        assert_eq!(list.selected(), Some(3));
+        // Book Two is still collapsed and shown for Gamma, but Delta no longer matches
+        list.set_filter("g");
+        assert_eq!(
+            list.order,
+            [Row::Group(0), Row::Item(0), Row::Group(1), Row::Item(2)]
+        );
+        assert_eq!((list.selected_line, list.top_line), (0, 0));
+    }
 }
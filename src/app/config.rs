@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use royal_api::CachePolicy;
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item};
+
+use super::theme::ThemeConfig;
+
+/// `config_dir()/royal_rust`, where the config and the fiction list live
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .expect("failed to find config_dir")
+        .join("royal_rust")
+}
+
+/// Contents of `config.toml`. Every field is optional in the file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// action name to key chords, e.g. `scroll_down = ["j", "down", "ctrl-n"]`
+    pub keys: HashMap<String, Vec<String>>,
+    /// name of a built-in theme or one of `themes`
+    pub theme: Option<String>,
+    pub themes: HashMap<String, ThemeConfig>,
+    pub reading: ReadingConfig,
+    pub typography: Typography,
+    pub layout: LayoutConfig,
+    pub chapters: ChapterListConfig,
+    pub fictions: FictionListConfig,
+    pub cache: CacheConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReadingConfig {
+    /// scrolling past the end of a chapter opens the next one
+    pub auto_advance: bool,
+    /// how many chapters after the open one are fetched in the background
+    pub prefetch_depth: usize,
+    /// defaults for `/` search, both can be toggled in the prompt
+    pub search_ignore_case: bool,
+    pub search_regex: bool,
+    /// where `typography.hyphenate` used to be, still read when that is not set
+    #[serde(skip_serializing)]
+    pub hyphenate: Option<bool>,
+}
+
+impl Default for ReadingConfig {
+    fn default() -> Self {
+        Self {
+            auto_advance: false,
+            prefetch_depth: 2,
+            search_ignore_case: true,
+            search_regex: false,
+            hyphenate: None,
+        }
+    }
+}
+
+/// Layout of the reading pane, changed live from the typography popup which saves it back.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Typography {
+    /// widest a line gets, centered in a wider pane, or 0 to fill the pane
+    pub max_width: u16,
+    /// stretch the spaces so every line but a paragraph's last ends at the edge
+    pub justify: bool,
+    /// spaces before the first line of a paragraph
+    pub indent: u16,
+    /// blank lines between the lines of a paragraph
+    pub line_spacing: u16,
+    /// blank lines between paragraphs
+    pub paragraph_spacing: u16,
+    /// split long words with a hyphen at the end of a line instead of moving them down
+    pub hyphenate: bool,
+    /// turn pages instead of scrolling, two pages side by side when the pane is wide enough
+    pub paged: bool,
+}
+
+impl Default for Typography {
+    fn default() -> Self {
+        Self {
+            max_width: 0,
+            justify: false,
+            indent: 0,
+            line_spacing: 0,
+            paragraph_spacing: 1,
+            hyphenate: false,
+            paged: false,
+        }
+    }
+}
+
+/// A column of the chapter list, in the order given by `[chapters] columns`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterColumn {
+    /// position in release order, from 1
+    Number,
+    Title,
+    /// release date in the local timezone
+    Date,
+    /// time since release, like `3d`
+    Age,
+    /// known once the chapter was downloaded
+    Words,
+    ReadingTime,
+    /// locked, read or new, and edited
+    Icons,
+}
+
+/// Orders of the chapter list, each listing the newest first.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterSort {
+    #[default]
+    Release,
+    /// by the last edit, for chapters that have been downloaded
+    Edited,
+}
+
+impl ChapterSort {
+    pub fn next(self) -> ChapterSort {
+        match self {
+            ChapterSort::Release => ChapterSort::Edited,
+            ChapterSort::Edited => ChapterSort::Release,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChapterSort::Release => "release",
+            ChapterSort::Edited => "edited",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ChapterListConfig {
+    pub columns: Vec<ChapterColumn>,
+    /// for the reading time column
+    pub words_per_minute: usize,
+    pub sort: ChapterSort,
+    /// oldest first instead
+    pub reversed: bool,
+}
+
+impl Default for ChapterListConfig {
+    fn default() -> Self {
+        Self {
+            columns: vec![
+                ChapterColumn::Icons,
+                ChapterColumn::Number,
+                ChapterColumn::Title,
+                ChapterColumn::Words,
+                ChapterColumn::Age,
+            ],
+            words_per_minute: 250,
+            sort: ChapterSort::default(),
+            reversed: false,
+        }
+    }
+}
+
+/// Orders of the fiction list.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FictionSort {
+    /// newest addition first
+    #[default]
+    Added,
+    Title,
+    /// newest chapter first
+    Updated,
+    /// most unread chapters first
+    Unread,
+    Author,
+}
+
+impl FictionSort {
+    pub fn next(self) -> FictionSort {
+        match self {
+            FictionSort::Added => FictionSort::Title,
+            FictionSort::Title => FictionSort::Updated,
+            FictionSort::Updated => FictionSort::Unread,
+            FictionSort::Unread => FictionSort::Author,
+            FictionSort::Author => FictionSort::Added,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FictionSort::Added => "added",
+            FictionSort::Title => "title",
+            FictionSort::Updated => "updated",
+            FictionSort::Unread => "unread",
+            FictionSort::Author => "author",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FictionListConfig {
+    /// two lines per fiction, the second with author, last update and status
+    pub expanded: bool,
+    pub sort: FictionSort,
+    /// the sort order upside down
+    pub reversed: bool,
+    /// name of the shelf shown, every fiction when unset
+    pub shelf: Option<String>,
+}
+
+/// Placement of the panes, saved whenever the sidebar is resized.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// percentage of the terminal width the sidebar takes
+    pub sidebar_width: u16,
+}
+
+impl LayoutConfig {
+    pub const MIN_SIDEBAR_WIDTH: u16 = 10;
+    pub const MAX_SIDEBAR_WIDTH: u16 = 70;
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self { sidebar_width: 30 }
+    }
+}
+
+/// How long downloaded pages are kept, in minutes, before asking the server again.
+/// 0 does not cache that kind of page at all.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// fiction pages, with the chapter list
+    pub fiction_max_age: u64,
+    pub chapter_max_age: u64,
+    /// covers
+    pub image_max_age: u64,
+    pub other_max_age: u64,
+    /// megabytes on disk, the oldest pages are dropped past it; 0 for no limit
+    pub max_size: u64,
+}
+
+impl CacheConfig {
+    pub fn policy(&self) -> CachePolicy {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        CachePolicy {
+            fiction_max_age: minutes(self.fiction_max_age),
+            chapter_max_age: minutes(self.chapter_max_age),
+            image_max_age: minutes(self.image_max_age),
+            other_max_age: minutes(self.other_max_age),
+            max_size: self.max_size * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        let policy = CachePolicy::default();
+        let minutes = |d: Duration| d.as_secs() / 60;
+        Self {
+            fiction_max_age: minutes(policy.fiction_max_age),
+            chapter_max_age: minutes(policy.chapter_max_age),
+            image_max_age: minutes(policy.image_max_age),
+            other_max_age: minutes(policy.other_max_age),
+            max_size: policy.max_size / (1024 * 1024),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        config_dir().join("config.toml")
+    }
+
+    /// A missing file is the default config, a broken one is an error.
+    pub fn load() -> Result<Config, String> {
+        let text = match read_to_string(Self::path()) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("failed to read config.toml: {}", e)),
+        };
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Result<Config, String> {
+        let mut config: Config =
+            toml::from_str(text).map_err(|e| format!("invalid config.toml: {}", e))?;
+        if let Some(hyphenate) = config.reading.hyphenate {
+            let table = text.parse::<toml::Table>().unwrap_or_default();
+            let moved = table
+                .get("typography")
+                .and_then(|t| t.get("hyphenate"))
+                .is_some();
+            if !moved {
+                config.typography.hyphenate = hyphenate;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Replaces the `[name]` table of `config.toml` with `value`, see `replace_section`.
+    pub fn save_section(name: &str, value: &impl Serialize) -> Result<(), String> {
+        let text = match read_to_string(Self::path()) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("failed to read config.toml: {}", e)),
+        };
+        let text = replace_section(&text, name, value)?;
+        create_dir_all(config_dir())
+            .and_then(|()| write(Self::path(), text))
+            .map_err(|e| format!("failed to save config.toml: {}", e))
+    }
+}
+
+/// Replaces the `[name]` table of the TOML document `text` with `value`. Comments, key order
+/// and formatting are kept, in that table as far as its keys still exist and everywhere
+/// else in the document.
+fn replace_section(text: &str, name: &str, value: &impl Serialize) -> Result<String, String> {
+    let mut document = text
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("invalid config.toml: {}", e))?;
+    // serialized by the same crate that reads the config, then taken apart
+    let section = toml::to_string(value)
+        .map_err(|e| e.to_string())?
+        .parse::<DocumentMut>()
+        .map_err(|e| e.to_string())?;
+    match document.get_mut(name).and_then(Item::as_table_mut) {
+        Some(table) => {
+            table.retain(|key, _| section.contains_key(key));
+            for (key, item) in section.iter() {
+                match (table.get_mut(key), item) {
+                    (Some(Item::Value(old)), Item::Value(new)) => {
+                        let decor = old.decor().clone();
+                        *old = new.clone();
+                        *old.decor_mut() = decor;
+                    }
+                    _ => {
+                        table.insert(key, item.clone());
+                    }
+                }
+            }
+        }
+        None => {
+            document.insert(name, Item::Table(section.as_table().clone()));
+        }
+    }
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_section_keeps_comments_and_other_tables() {
+        let text = "\
+# my settings
+theme = \"light\"
+
+[layout]
+# wide enough for two columns
+sidebar_width = 40 # percent
+
+[keys]
+quit = [\"Q\"]
+";
+        let layout = LayoutConfig { sidebar_width: 25 };
+        let saved = replace_section(text, "layout", &layout).unwrap();
+        assert_eq!(saved, text.replace("= 40", "= 25"));
+    }
+
+    #[test]
+    fn replace_section_adds_missing_tables_and_drops_unset_keys() {
+        let text = "[fictions]\nsort = \"title\"\nshelf = \"Reading\"\n";
+        let fictions = FictionListConfig::default();
+        let saved = replace_section(text, "fictions", &fictions).unwrap();
+        let parsed: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(parsed.fictions.sort, FictionSort::Added);
+        assert_eq!(parsed.fictions.shelf, None);
+        let saved = replace_section("", "layout", &LayoutConfig::default()).unwrap();
+        assert_eq!(saved.trim(), "[layout]\nsidebar_width = 30");
+    }
+
+    #[test]
+    fn hyphenate_is_still_read_from_reading() {
+        let config = Config::parse("[reading]\nhyphenate = true\n").unwrap();
+        assert!(config.typography.hyphenate);
+        let text = "[reading]\nhyphenate = true\n[typography]\nhyphenate = false\n";
+        assert!(!Config::parse(text).unwrap().typography.hyphenate);
+    }
+}
@@ -0,0 +1,58 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    text::Line,
+    widgets::{Block, Borders, Clear, Widget},
+};
+
+use super::text;
+use super::theme::Theme;
+
+/// A yes or no question, like whether to really delete a fiction.
+pub struct ConfirmWidget<'a> {
+    question: &'a str,
+    theme: Theme,
+}
+
+impl<'a> ConfirmWidget<'a> {
+    pub fn new(question: &'a str, theme: Theme) -> ConfirmWidget<'a> {
+        Self { question, theme }
+    }
+
+    /// the width of the popup for `question`, given the terminal is `max` wide
+    pub fn width(question: &str, max: u16) -> u16 {
+        (text::width(question) as u16 + 6).clamp(30, 70).min(max)
+    }
+}
+
+impl Widget for ConfirmWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+        Block::new()
+            .title(" Confirm ")
+            .style(self.theme.text)
+            .borders(Borders::ALL)
+            .render(area, buf);
+        let width = area.width.saturating_sub(4);
+        if width == 0 || area.height < 3 {
+            return;
+        }
+        buf.set_line(
+            area.x + 2,
+            area.y + 1,
+            &Line::styled(
+                text::truncate(self.question, width as usize, "…"),
+                self.theme.text,
+            ),
+            width,
+        );
+        if area.height >= 5 {
+            buf.set_line(
+                area.x + 2,
+                area.y + 3,
+                &Line::styled("y confirms, n or esc cancels", self.theme.dim),
+                width,
+            );
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, StatefulWidget, Widget},
+};
+
+use royal_api::{FictionIndex, IndexHit};
+
+use super::input::{TextInput, TextInputWidget};
+use super::theme::Theme;
+
+pub struct FullTextWidget {
+    theme: Theme,
+}
+
+/// Search over the downloaded chapters of one fiction.
+pub struct FullTextState {
+    pub fiction: usize,
+    pub fiction_title: String,
+    pub input: TextInput,
+    pub hits: Vec<IndexHit>,
+    pub selected: usize,
+    top: usize,
+    /// query the current hits belong to
+    searched: String,
+    /// number of chapters in the index, shown as a hint
+    indexed: usize,
+}
+
+impl FullTextState {
+    pub fn new(fiction: usize, fiction_title: String) -> FullTextState {
+        Self {
+            fiction,
+            fiction_title,
+            input: TextInput::default(),
+            hits: Vec::new(),
+            selected: 0,
+            top: 0,
+            searched: String::new(),
+            indexed: 0,
+        }
+    }
+
+    /// reruns the query if it changed since the last call
+    pub fn update(&mut self, index: &FictionIndex) {
+        self.indexed = index.len();
+        if self.input.text == self.searched {
+            return;
+        }
+        self.searched = self.input.text.clone();
+        self.hits = index.search(&self.searched);
+        self.selected = 0;
+        self.top = 0;
+    }
+
+    pub fn selected_hit(&self) -> Option<&IndexHit> {
+        self.hits.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.hits.len().saturating_sub(1));
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+impl FullTextWidget {
+    pub fn new(theme: Theme) -> FullTextWidget {
+        Self { theme }
+    }
+}
+
+impl StatefulWidget for FullTextWidget {
+    type State = FullTextState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        Clear.render(area, buf);
+        Block::new()
+            .title(format!(" Search {} ", state.fiction_title))
+            .style(self.theme.text)
+            .borders(Borders::ALL)
+            .render(area, buf);
+        let inner = Rect {
+            x: area.x + 2,
+            y: area.y + 1,
+            width: area.width.saturating_sub(4),
+            height: area.height.saturating_sub(2),
+        };
+        if inner.width == 0 || inner.height < 2 {
+            return;
+        }
+        TextInputWidget::new(self.theme).render(Rect { height: 1, ..inner }, buf, &mut state.input);
+        let status = format!(
+            "{} hits in {} downloaded chapters",
+            state.hits.len(),
+            state.indexed
+        );
+        buf.set_line(
+            inner.x,
+            inner.y + 1,
+            &Line::styled(status, self.theme.dim),
+            inner.width,
+        );
+        let body = Rect {
+            y: inner.y + 3,
+            height: inner.height.saturating_sub(3),
+            ..inner
+        };
+        // each hit takes a title line and a snippet line
+        let height = body.height as usize / 2;
+        if height == 0 {
+            return;
+        }
+        state.top = state
+            .top
+            .clamp((state.selected + 1).saturating_sub(height), state.selected);
+        for (i, hit) in state.hits.iter().enumerate().skip(state.top).take(height) {
+            let y = body.y + (i - state.top) as u16 * 2;
+            let title_style = if i == state.selected {
+                self.theme.selection
+            } else {
+                self.theme.border
+            };
+            buf.set_line(
+                body.x,
+                y,
+                &Line::from(vec![
+                    Span::styled(hit.chapter_title.as_str(), title_style),
+                    Span::styled(format!("  ¶{}", hit.paragraph + 1), self.theme.dim),
+                ]),
+                body.width,
+            );
+            buf.set_line(
+                body.x + 2,
+                y + 1,
+                &Line::styled(hit.snippet.as_str(), self.theme.text),
+                body.width.saturating_sub(2),
+            );
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, StatefulWidget, Widget},
+};
+
+use super::keymap::{Action, Context, Keymap};
+use super::text;
+use super::theme::Theme;
+
+/// Keys of the prompts and popups. They are fixed, so they are not in the keymap.
+const INPUT_KEYS: &[(&str, &str)] = &[
+    ("enter", "confirm"),
+    ("esc", "cancel, or clear the sidebar filter"),
+    ("up, down, tab", "move through results"),
+    ("ctrl-n, ctrl-p", "move through results"),
+    ("left, right", "move the cursor"),
+    ("ctrl-a, home", "start of line"),
+    ("ctrl-e, end", "end of line"),
+    ("ctrl-w", "delete word"),
+    ("ctrl-u", "clear"),
+    ("ctrl-t", "match case or not, in /"),
+    ("ctrl-r", "regex or plain, in /"),
+];
+
+pub struct HelpWidget<'a> {
+    keymap: &'a Keymap,
+    theme: Theme,
+}
+
+/// The help popup, listing every key by where it is used.
+#[derive(Default)]
+pub struct HelpState {
+    /// first line shown
+    scroll: usize,
+    /// lines that fit, from the last render
+    height: usize,
+}
+
+impl HelpState {
+    /// the render clamps this to the end of the list
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_add(lines);
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.height.max(1));
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.height.max(1));
+    }
+}
+
+impl<'a> HelpWidget<'a> {
+    pub fn new(keymap: &'a Keymap, theme: Theme) -> HelpWidget<'a> {
+        Self { keymap, theme }
+    }
+
+    /// every section, as (keys, description) rows under a heading
+    fn sections(&self) -> Vec<(&'static str, Vec<(String, &'static str)>)> {
+        let mut sections = Context::ALL
+            .iter()
+            .map(|context| {
+                let rows = Action::ALL
+                    .iter()
+                    .filter(|a| a.context() == *context)
+                    .map(|a| {
+                        let keys = self
+                            .keymap
+                            .keys(*a)
+                            .iter()
+                            .map(|k| k.to_string())
+                            .collect::<Vec<_>>();
+                        let keys = if keys.is_empty() {
+                            String::from("unbound")
+                        } else {
+                            keys.join(", ")
+                        };
+                        (keys, a.description())
+                    })
+                    .collect();
+                (context.name(), rows)
+            })
+            .collect::<Vec<_>>();
+        let input = INPUT_KEYS
+            .iter()
+            .map(|(keys, description)| (keys.to_string(), *description))
+            .collect();
+        sections.push(("input", input));
+        sections
+    }
+}
+
+impl StatefulWidget for HelpWidget<'_> {
+    type State = HelpState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        Clear.render(area, buf);
+        Block::new()
+            .title(" Keys ")
+            .title_bottom(Line::styled(" j/k scroll, esc closes ", self.theme.dim))
+            .style(self.theme.text)
+            .borders(Borders::ALL)
+            .render(area, buf);
+        let inner = Rect {
+            x: area.x + 2,
+            y: area.y + 1,
+            width: area.width.saturating_sub(4),
+            height: area.height.saturating_sub(2),
+        };
+        if inner.width == 0 || inner.height == 0 {
+            return;
+        }
+        let sections = self.sections();
+        let key_width = sections
+            .iter()
+            .flat_map(|(_, rows)| rows.iter().map(|(keys, _)| text::width(keys)))
+            .max()
+            .unwrap_or(0)
+            .min(inner.width as usize / 2);
+        let mut lines = Vec::new();
+        for (name, rows) in sections {
+            if !lines.is_empty() {
+                lines.push(Line::raw(""));
+            }
+            lines.push(Line::styled(
+                name,
+                self.theme.border.add_modifier(Modifier::BOLD),
+            ));
+            for (keys, description) in rows {
+                let keys = text::truncate(&keys, key_width, "…");
+                let padding = key_width - text::width(&keys);
+                lines.push(Line::from(vec![
+                    Span::raw(format!("  {}{}  ", keys, " ".repeat(padding))),
+                    Span::styled(description, self.theme.dim),
+                ]));
+            }
+        }
+        state.height = inner.height as usize;
+        state.scroll = state.scroll.min(lines.len().saturating_sub(state.height));
+        for (row, line) in lines
+            .iter()
+            .skip(state.scroll)
+            .take(state.height)
+            .enumerate()
+        {
+            buf.set_line(inner.x, inner.y + row as u16, line, inner.width);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use royal_api::Chapter;
+
+/// What is known about a chapter once it has been downloaded.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ChapterInfo {
+    pub words: usize,
+    pub published: u64,
+    pub edited: u64,
+}
+
+impl ChapterInfo {
+    /// edits within the first hour are usually typo fixes, not worth a marker
+    pub fn is_edited(&self) -> bool {
+        self.edited > self.published + 60 * 60
+    }
+}
+
+/// Reading history, saved as `data_dir()/royal_rust/history.json`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct History {
+    /// paths of the chapters that were opened
+    read: HashSet<String>,
+    /// by chapter path
+    chapters: HashMap<String, ChapterInfo>,
+    /// when each fiction's chapter list was last shown, by fiction id
+    seen: HashMap<usize, u64>,
+}
+
+impl History {
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("royal_rust").join("history.json"))
+    }
+
+    /// A missing file is an empty history, a broken one is an error.
+    pub fn load() -> Result<History, String> {
+        let Some(path) = Self::path() else {
+            return Ok(History::default());
+        };
+        match read_to_string(path) {
+            Ok(text) => {
+                serde_json::from_str(&text).map_err(|e| format!("invalid history.json: {}", e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(History::default()),
+            Err(e) => Err(format!("failed to read history.json: {}", e)),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write(path, serde_json::to_string(self)?)
+    }
+
+    pub fn mark_read(&mut self, path: &str) {
+        self.read.insert(path.to_string());
+    }
+
+    pub fn is_read(&self, path: &str) -> bool {
+        self.read.contains(path)
+    }
+
+    /// remembers the word count and dates of a downloaded chapter
+    pub fn record(&mut self, chapter: &Chapter) {
+        let words = chapter
+            .content
+            .iter()
+            .map(|p| p.split_whitespace().count())
+            .sum();
+        self.chapters.insert(
+            chapter.path.clone(),
+            ChapterInfo {
+                words,
+                published: chapter.published,
+                edited: chapter.edited,
+            },
+        );
+    }
+
+    pub fn info(&self, path: &str) -> Option<&ChapterInfo> {
+        self.chapters.get(path)
+    }
+
+    /// when the chapters of `fiction` were last shown, if ever
+    pub fn seen(&self, fiction: usize) -> Option<u64> {
+        self.seen.get(&fiction).copied()
+    }
+
+    pub fn set_seen(&mut self, fiction: usize, time: u64) {
+        self.seen.insert(fiction, time);
+    }
+}
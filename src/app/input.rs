@@ -0,0 +1,172 @@
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    layout::Rect,
+    style::Modifier,
+    text::{Line, Span},
+    widgets::StatefulWidget,
+};
+
+use super::text;
+use super::theme::Theme;
+
+/// Single line text field, draws the text and a block cursor.
+pub struct TextInputWidget {
+    theme: Theme,
+}
+
+#[derive(Default)]
+pub struct TextInput {
+    pub text: String,
+    /// cursor position in chars, not bytes
+    pub cursor: usize,
+    pub error: Option<String>,
+}
+
+impl TextInput {
+    /// Applies the line editing keys, returns false if the key is not one of them.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('u') if ctrl => self.clear(),
+            KeyCode::Char('w') if ctrl => self.delete_word(),
+            KeyCode::Char('a') if ctrl => self.home(),
+            KeyCode::Char('e') if ctrl => self.end(),
+            KeyCode::Char(c) if !ctrl => self.insert(c),
+            KeyCode::Left => self.left(),
+            KeyCode::Right => self.right(),
+            KeyCode::Home => self.home(),
+            KeyCode::End => self.end(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(cursor)
+            .map(|(i, _)| i)
+            .unwrap_or(self.text.len())
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte_index(self.cursor);
+        self.text.insert(i, c);
+        self.cursor += 1;
+        self.error = None;
+    }
+
+    /// pasted text is flattened onto one line
+    pub fn insert_str(&mut self, s: &str) {
+        for c in s.chars().filter(|c| !c.is_control()) {
+            self.insert(c);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.delete();
+        }
+    }
+
+    pub fn delete(&mut self) {
+        let i = self.byte_index(self.cursor);
+        if i < self.text.len() {
+            self.text.remove(i);
+        }
+        self.error = None;
+    }
+
+    pub fn delete_word(&mut self) {
+        let chars = self.text.chars().collect::<Vec<_>>();
+        let mut start = self.cursor;
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        let (from, to) = (self.byte_index(start), self.byte_index(self.cursor));
+        self.text.replace_range(from..to, "");
+        self.cursor = start;
+        self.error = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.error = None;
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.chars().count();
+    }
+}
+
+impl TextInputWidget {
+    pub fn new(theme: Theme) -> TextInputWidget {
+        Self { theme }
+    }
+}
+
+impl StatefulWidget for TextInputWidget {
+    type State = TextInput;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let width = area.width as usize;
+        if width == 0 || area.height == 0 {
+            return;
+        }
+        // keep the cursor in view by scrolling the text horizontally, by columns since
+        // wide chars take two
+        let chars = state.text.chars().collect::<Vec<_>>();
+        let columns = |c: &char| text::width(c.encode_utf8(&mut [0; 4]));
+        let mut start = state.cursor.min(chars.len());
+        let mut used = chars.get(start).map_or(1, columns);
+        while start > 0 && used + columns(&chars[start - 1]) <= width {
+            start -= 1;
+            used += columns(&chars[start]);
+        }
+        let mut spans = Vec::new();
+        for (i, c) in chars.iter().skip(start).enumerate() {
+            let style = if start + i == state.cursor {
+                self.theme.text.add_modifier(Modifier::REVERSED)
+            } else {
+                self.theme.text
+            };
+            spans.push(Span::styled(c.to_string(), style));
+        }
+        if state.cursor >= chars.len() {
+            spans.push(Span::styled(
+                " ",
+                self.theme.text.add_modifier(Modifier::REVERSED),
+            ));
+        }
+        buf.set_line(area.x, area.y, &Line::from(spans), area.width);
+        if let Some(error) = &state.error {
+            if area.height > 1 {
+                buf.set_line(
+                    area.x,
+                    area.y + 1,
+                    &Line::styled(error.as_str(), self.theme.error),
+                    area.width,
+                );
+            }
+        }
+    }
+}
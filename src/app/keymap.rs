@@ -0,0 +1,558 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    ScrollDown,
+    ScrollUp,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    JumpPercent,
+    NextChapter,
+    PrevChapter,
+    SearchText,
+    NextMatch,
+    PrevMatch,
+    ClearSearch,
+    SearchFiction,
+    Typography,
+    TogglePaged,
+    Fullscreen,
+    SidebarWider,
+    SidebarNarrower,
+    ToggleExpanded,
+    SidebarDown,
+    SidebarUp,
+    Select,
+    Back,
+    AddFiction,
+    Reverse,
+    CycleSort,
+    FilterSidebar,
+    GoToChapter,
+    ToggleVolumes,
+    NextShelf,
+    PrevShelf,
+    MoveToNextShelf,
+    MoveToPrevShelf,
+    EditTags,
+    NewShelf,
+    RenameShelf,
+    Delete,
+    Undo,
+    Help,
+}
+
+/// Where an action is used, to group the help by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    General,
+    Sidebar,
+    Reading,
+    Search,
+}
+
+impl Context {
+    pub const ALL: &'static [Context] = &[
+        Context::General,
+        Context::Sidebar,
+        Context::Reading,
+        Context::Search,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Context::General => "general",
+            Context::Sidebar => "sidebar",
+            Context::Reading => "reading",
+            Context::Search => "search",
+        }
+    }
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::ScrollDown,
+        Action::ScrollUp,
+        Action::PageDown,
+        Action::PageUp,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::Top,
+        Action::Bottom,
+        Action::JumpPercent,
+        Action::NextChapter,
+        Action::PrevChapter,
+        Action::SearchText,
+        Action::NextMatch,
+        Action::PrevMatch,
+        Action::ClearSearch,
+        Action::SearchFiction,
+        Action::Typography,
+        Action::TogglePaged,
+        Action::Fullscreen,
+        Action::SidebarWider,
+        Action::SidebarNarrower,
+        Action::ToggleExpanded,
+        Action::SidebarDown,
+        Action::SidebarUp,
+        Action::Select,
+        Action::Back,
+        Action::AddFiction,
+        Action::Reverse,
+        Action::CycleSort,
+        Action::FilterSidebar,
+        Action::GoToChapter,
+        Action::ToggleVolumes,
+        Action::NextShelf,
+        Action::PrevShelf,
+        Action::MoveToNextShelf,
+        Action::MoveToPrevShelf,
+        Action::EditTags,
+        Action::NewShelf,
+        Action::RenameShelf,
+        Action::Delete,
+        Action::Undo,
+        Action::Help,
+    ];
+
+    pub fn context(self) -> Context {
+        match self {
+            Action::Quit | Action::Help => Context::General,
+            Action::ScrollDown
+            | Action::ScrollUp
+            | Action::PageDown
+            | Action::PageUp
+            | Action::HalfPageDown
+            | Action::HalfPageUp
+            | Action::Top
+            | Action::Bottom
+            | Action::JumpPercent
+            | Action::NextChapter
+            | Action::PrevChapter
+            | Action::Typography
+            | Action::TogglePaged
+            | Action::Fullscreen => Context::Reading,
+            Action::SearchText
+            | Action::NextMatch
+            | Action::PrevMatch
+            | Action::ClearSearch
+            | Action::SearchFiction
+            | Action::AddFiction => Context::Search,
+            Action::SidebarWider
+            | Action::SidebarNarrower
+            | Action::ToggleExpanded
+            | Action::SidebarDown
+            | Action::SidebarUp
+            | Action::Select
+            | Action::Back
+            | Action::Reverse
+            | Action::CycleSort
+            | Action::FilterSidebar
+            | Action::GoToChapter
+            | Action::ToggleVolumes
+            | Action::NextShelf
+            | Action::PrevShelf
+            | Action::MoveToNextShelf
+            | Action::MoveToPrevShelf
+            | Action::EditTags
+            | Action::NewShelf
+            | Action::RenameShelf
+            | Action::Delete
+            | Action::Undo => Context::Sidebar,
+        }
+    }
+
+    /// name used in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::ScrollDown => "scroll_down",
+            Action::ScrollUp => "scroll_up",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::JumpPercent => "jump_percent",
+            Action::NextChapter => "next_chapter",
+            Action::PrevChapter => "prev_chapter",
+            Action::SearchText => "search_text",
+            Action::NextMatch => "next_match",
+            Action::PrevMatch => "prev_match",
+            Action::ClearSearch => "clear_search",
+            Action::SearchFiction => "search_fiction",
+            Action::Typography => "typography",
+            Action::TogglePaged => "toggle_paged",
+            Action::Fullscreen => "fullscreen",
+            Action::SidebarWider => "sidebar_wider",
+            Action::SidebarNarrower => "sidebar_narrower",
+            Action::ToggleExpanded => "toggle_expanded",
+            Action::SidebarDown => "sidebar_down",
+            Action::SidebarUp => "sidebar_up",
+            Action::Select => "select",
+            Action::Back => "back",
+            Action::AddFiction => "add_fiction",
+            Action::Reverse => "reverse",
+            Action::CycleSort => "cycle_sort",
+            Action::FilterSidebar => "filter_sidebar",
+            Action::GoToChapter => "go_to_chapter",
+            Action::ToggleVolumes => "toggle_volumes",
+            Action::NextShelf => "next_shelf",
+            Action::PrevShelf => "prev_shelf",
+            Action::MoveToNextShelf => "move_to_next_shelf",
+            Action::MoveToPrevShelf => "move_to_prev_shelf",
+            Action::EditTags => "edit_tags",
+            Action::NewShelf => "new_shelf",
+            Action::RenameShelf => "rename_shelf",
+            Action::Delete => "delete",
+            Action::Undo => "undo",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::ScrollDown => "scroll down",
+            Action::ScrollUp => "scroll up",
+            Action::PageDown => "page down",
+            Action::PageUp => "page up",
+            Action::HalfPageDown => "half page down",
+            Action::HalfPageUp => "half page up",
+            Action::Top => "top of chapter",
+            Action::Bottom => "end of chapter",
+            Action::JumpPercent => "jump to N%",
+            Action::NextChapter => "next chapter",
+            Action::PrevChapter => "previous chapter",
+            Action::SearchText => "search in chapter",
+            Action::NextMatch => "next match",
+            Action::PrevMatch => "previous match",
+            Action::ClearSearch => "clear search",
+            Action::SearchFiction => "search downloaded chapters",
+            Action::Typography => "typography",
+            Action::TogglePaged => "paged mode",
+            Action::Fullscreen => "fullscreen",
+            Action::SidebarWider => "widen sidebar",
+            Action::SidebarNarrower => "narrow sidebar",
+            Action::ToggleExpanded => "expanded fiction rows",
+            Action::SidebarDown => "sidebar scroll down",
+            Action::SidebarUp => "sidebar scroll up",
+            Action::Select => "open fiction or chapter, fold volume",
+            Action::Back => "back to fictions",
+            Action::AddFiction => "new fiction",
+            Action::Reverse => "reverse sort order",
+            Action::CycleSort => "change sort mode",
+            Action::FilterSidebar => "filter sidebar, #tag filters fictions by tag",
+            Action::GoToChapter => "go to chapter",
+            Action::ToggleVolumes => "collapse or expand all volumes",
+            Action::NextShelf => "next shelf",
+            Action::PrevShelf => "previous shelf",
+            Action::MoveToNextShelf => "move fiction to next shelf",
+            Action::MoveToPrevShelf => "move fiction to previous shelf",
+            Action::EditTags => "edit fiction tags",
+            Action::NewShelf => "add a shelf",
+            Action::RenameShelf => "rename the shelf shown",
+            Action::Delete => "delete fiction",
+            Action::Undo => "undo delete",
+            Action::Help => "list all keys",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::ScrollDown => &["j", "down"],
+            Action::ScrollUp => &["k", "up"],
+            Action::PageDown => &["space", "pagedown", "ctrl-f"],
+            Action::PageUp => &["b", "pageup", "ctrl-b"],
+            Action::HalfPageDown => &["ctrl-d"],
+            Action::HalfPageUp => &["ctrl-u"],
+            Action::Top => &["g", "home"],
+            Action::Bottom => &["G", "end"],
+            Action::JumpPercent => &["%"],
+            Action::NextChapter => &["]"],
+            Action::PrevChapter => &["["],
+            Action::SearchText => &["/"],
+            Action::NextMatch => &["n"],
+            Action::PrevMatch => &["N"],
+            Action::ClearSearch => &["esc"],
+            Action::SearchFiction => &["F"],
+            Action::Typography => &["t"],
+            Action::TogglePaged => &["p"],
+            Action::Fullscreen => &["f"],
+            Action::SidebarWider => &[">"],
+            Action::SidebarNarrower => &["<"],
+            Action::ToggleExpanded => &["v"],
+            Action::SidebarDown => &["J"],
+            Action::SidebarUp => &["K"],
+            Action::Select => &["l", "right", "enter"],
+            Action::Back => &["h", "left"],
+            Action::AddFiction => &["o"],
+            Action::Reverse => &["r"],
+            Action::CycleSort => &["s"],
+            Action::FilterSidebar => &["i"],
+            Action::GoToChapter => &[":"],
+            Action::ToggleVolumes => &["z"],
+            Action::NextShelf => &["tab"],
+            Action::PrevShelf => &["backtab"],
+            Action::MoveToNextShelf => &["m"],
+            Action::MoveToPrevShelf => &["M"],
+            Action::EditTags => &["T"],
+            Action::NewShelf => &["A"],
+            Action::RenameShelf => &["R"],
+            Action::Delete => &["d"],
+            Action::Undo => &["u"],
+            Action::Help => &["?"],
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name() == s)
+            .ok_or_else(|| format!("unknown action \"{}\"", s))
+    }
+}
+
+/// A key plus modifiers, written like `j`, `J`, `ctrl-d`, `alt-pagedown` or `space`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+];
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        let modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let modifiers = match code {
+            // shift is already part of the key, `J` and `shift-j` are the same chord
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self { code, modifiers }
+    }
+
+    pub fn from_event(key: KeyEvent) -> KeyChord {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid key \"{}\"", s);
+        let mut parts = s.split('-').collect::<Vec<_>>();
+        // `-` on its own, or as the last key of a chord like `ctrl--`
+        if s.ends_with('-') {
+            parts.retain(|p| !p.is_empty());
+            parts.push("-");
+        }
+        let (key, modifier_names) = parts.split_last().ok_or_else(err)?;
+        let mut modifiers = KeyModifiers::NONE;
+        for m in modifier_names {
+            modifiers |= match m.to_ascii_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(err()),
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let lower = key.to_ascii_lowercase();
+                match KEY_NAMES.iter().find(|(name, _)| *name == lower) {
+                    Some((_, KeyCode::Tab)) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        KeyCode::BackTab
+                    }
+                    Some((_, code)) => *code,
+                    None => match lower.strip_prefix('f').map(str::parse::<u8>) {
+                        Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                        _ => return Err(err()),
+                    },
+                }
+            }
+        };
+        Ok(KeyChord::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            f.write_str("shift-")?;
+        }
+        match self.code {
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Char(c) if c != ' ' => write!(f, "{}", c),
+            code => match KEY_NAMES.iter().find(|(_, x)| *x == code) {
+                Some((name, _)) => f.write_str(name),
+                None => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// Maps key chords to actions. Starts from the defaults, and every action named in the
+/// config replaces its default keys.
+pub struct Keymap {
+    actions: HashMap<KeyChord, Action>,
+    keys: HashMap<Action, Vec<KeyChord>>,
+}
+
+impl Keymap {
+    /// Builds the keymap from the `[keys]` config table. Invalid entries are skipped and
+    /// reported, so one typo does not leave the app without bindings. Binding a default key
+    /// of another action moves it; only two configured actions sharing a key is a conflict.
+    pub fn new(config: &HashMap<String, Vec<String>>) -> (Keymap, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut keys = HashMap::new();
+        let mut configured = HashSet::new();
+        for action in Action::ALL {
+            let chords = action
+                .default_keys()
+                .iter()
+                .map(|k| k.parse::<KeyChord>().unwrap())
+                .collect::<Vec<_>>();
+            keys.insert(*action, chords);
+        }
+        for (name, chords) in config {
+            let action = match name.parse::<Action>() {
+                Ok(action) => action,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            let chords = chords
+                .iter()
+                .filter_map(|k| k.parse::<KeyChord>().map_err(|e| errors.push(e)).ok())
+                .collect();
+            keys.insert(action, chords);
+            configured.insert(action);
+        }
+        // a configured key takes over from the action it is a default of
+        let claimed = configured
+            .iter()
+            .flat_map(|action| keys[action].clone())
+            .collect::<HashSet<_>>();
+        for (action, chords) in keys.iter_mut() {
+            if !configured.contains(action) {
+                chords.retain(|chord| !claimed.contains(chord));
+            }
+        }
+        let mut actions = HashMap::new();
+        for action in Action::ALL {
+            for chord in &keys[action] {
+                if let Some(other) = actions.insert(*chord, *action) {
+                    errors.push(format!(
+                        "\"{}\" is bound to both {} and {}",
+                        chord,
+                        other.name(),
+                        action.name()
+                    ));
+                }
+            }
+        }
+        (Keymap { actions, keys }, errors)
+    }
+
+    pub fn action(&self, key: KeyEvent) -> Option<Action> {
+        self.actions.get(&KeyChord::from_event(key)).copied()
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyChord] {
+        &self.keys[&action]
+    }
+
+    /// `key: label` pairs for the given actions, using each action's first key.
+    /// Unbound actions are left out.
+    pub fn describe(&self, hints: &[(Action, &str)]) -> String {
+        hints
+            .iter()
+            .filter_map(|(action, label)| {
+                let key = self.keys(*action).first()?;
+                Some(format!("{}: {}", key, label))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(name, keys)| {
+                (
+                    name.to_string(),
+                    keys.iter().map(|k| k.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn configured_key_takes_over_a_default() {
+        let (keymap, errors) = Keymap::new(&config(&[("quit", &["j"])]));
+        assert!(errors.is_empty(), "{:?}", errors);
+        let j = "j".parse::<KeyChord>().unwrap();
+        assert_eq!(keymap.keys(Action::Quit), &[j]);
+        assert!(!keymap.keys(Action::ScrollDown).contains(&j));
+        assert_eq!(keymap.actions[&j], Action::Quit);
+    }
+
+    #[test]
+    fn two_configured_actions_on_one_key_conflict() {
+        let (_, errors) = Keymap::new(&config(&[("quit", &["x"]), ("scroll_down", &["x"])]));
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use royal_api::{Fiction, RoyalClient, RoyalError};
+
+/// Loads the fictions of the library on a background thread, in the order they are
+/// listed, so the app starts at once even when the network is slow or down.
+pub struct Loader {
+    /// ids not yet received
+    remaining: usize,
+    rx: Receiver<(usize, Result<Fiction, RoyalError>)>,
+}
+
+impl Loader {
+    pub fn new(client: Arc<RoyalClient>, ids: Vec<usize>) -> Loader {
+        let (tx, rx) = channel();
+        let remaining = ids.len();
+        thread::spawn(move || {
+            for id in ids {
+                if tx.send((id, client.get_fiction(id))).is_err() {
+                    return;
+                }
+            }
+        });
+        Self { remaining, rx }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// whatever finished since the last call, failures included
+    pub fn poll(&mut self) -> Vec<(usize, Result<Fiction, RoyalError>)> {
+        let received = self.rx.try_iter().collect::<Vec<_>>();
+        self.remaining -= received.len();
+        received
+    }
+}
@@ -1,304 +1,1787 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::io::{stdout, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use chap_list::{ListState, ListWidget};
+use chap_list::{
+    last_update, now, unread, volume_groups, ChapterContext, FictionContext, ListState, ListWidget,
+    Listable,
+};
+use config::{config_dir, ChapterSort, Config, FictionSort, LayoutConfig};
+use confirm::ConfirmWidget;
+use fulltext::{FullTextState, FullTextWidget};
+use help::{HelpState, HelpWidget};
+use history::History;
+use input::{TextInput, TextInputWidget};
+use keymap::{Action, Keymap};
+use loader::Loader;
+use prefetch::Prefetcher;
 use reading_window::{ReadingWindow, ReadingWindowState};
+use search::{SearchState, SearchWidget, Searcher};
+use shelves::Shelves;
+use theme::Theme;
+use typography::{TypographyState, TypographyWidget};
 
 use ratatui::{
     backend::CrosstermBackend,
     crossterm::{
-        event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        event::{
+            self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste,
+            EnableMouseCapture, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton,
+            MouseEvent, MouseEventKind,
+        },
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
         ExecutableCommand,
     },
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
-    text::Line,
-    widgets::{Block, Borders, Paragraph, Widget},
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
     Frame, Terminal,
 };
-use royal_api::{Chapter, ChapterReference, Fiction, RoyalClient};
+use regex::RegexBuilder;
+use royal_api::{
+    tokenize, Chapter, ChapterReference, Fiction, FictionIndex, FictionLink, ResponseCache,
+    RoyalClient,
+};
 mod chap_list;
+mod config;
+mod confirm;
+mod fulltext;
+mod help;
+mod history;
+mod input;
+mod keymap;
+mod loader;
+mod prefetch;
 mod reading_window;
+mod search;
+mod shelves;
+mod text;
+mod theme;
+mod typography;
 
-const CONTROLS: &str = " q: quit, j: scroll down, k: scroll up, J: sidebar scroll down, K: sidebar scroll up, o: new fiction, l: select in sidebar, r: reverse sort order";
+/// margin of the sidebar `ListWidget`, needed again to map clicks to rows
+const SIDEBAR_MARGIN: (u16, u16) = (1, 1);
+/// lines one wheel notch scrolls the reading window
+const WHEEL_LINES: usize = 3;
+/// longest gap between the clicks of a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// The chapter in the reading window, by fiction id and index in release order.
+/// Kept apart from the sidebar, which may be showing something else.
+#[derive(Debug, Clone, Copy)]
+struct Reading {
+    fiction: usize,
+    chapter: usize,
+}
 
 pub struct App {
-    client: RoyalClient,
+    client: Arc<RoyalClient>,
+    prefetcher: Prefetcher,
+    searcher: Searcher,
+    loader: Loader,
+    /// line of each fiction in `fictions.txt`, which is the order they were added in
+    listed: HashMap<usize, usize>,
+    /// listed fictions that are still loading or failed to, written back on quit
+    unloaded: Vec<usize>,
+    config: Config,
+    keymap: Keymap,
+    theme: Theme,
     reading_state: ReadingWindowState,
     fiction_state: ListState<Fiction>,
     chapter_state: ListState<ChapterReference>,
     fictions_showing: bool,
-    fiction_in: Option<String>,
+    /// only the reading window is drawn, the footer comes back for prompts and errors
+    fullscreen: bool,
+    /// where the panes were drawn last, for the mouse
+    sidebar_area: Rect,
+    reading_area: Rect,
+    /// time and sidebar line of the last click, to spot a double click
+    last_click: Option<(Instant, u16)>,
+    /// the sidebar border is being dragged
+    resizing: bool,
+    /// id of the fiction whose chapters `chapter_state` holds
+    chapters_fiction: Option<usize>,
+    /// when those chapters were shown before, what is newer is marked new
+    chapters_new_since: Option<u64>,
+    history: History,
+    shelves: Shelves,
+    /// index into `shelves.names` of the shelf the sidebar shows, every fiction if none
+    shelf: Option<usize>,
+    reading: Option<Reading>,
+    search: Option<SearchState>,
+    fulltext: Option<FullTextState>,
+    typography: Option<TypographyState>,
+    help: Option<HelpState>,
+    /// full-text indexes by fiction id, loaded on first use
+    indexes: HashMap<usize, FictionIndex>,
+    /// indexes with chapters added since they were last saved
+    dirty_indexes: HashSet<usize>,
+    index_dir: Option<PathBuf>,
+    /// the `/` prompt for searching the open chapter
+    chapter_search: Option<TextInput>,
+    search_ignore_case: bool,
+    search_regex: bool,
+    /// the prompt filtering whichever list the sidebar shows
+    sidebar_filter: Option<TextInput>,
+    /// the `:` prompt for jumping to a chapter
+    go_to: Option<TextInput>,
+    /// the prompt for the tags of a fiction, by id
+    tags: Option<(usize, TextInput)>,
+    /// the prompt naming a new shelf, or a new name for the shelf at this index
+    shelf_name: Option<(Option<usize>, TextInput)>,
+    /// id of the fiction waiting for its deletion to be confirmed
+    confirm_delete: Option<usize>,
+    /// deleted fictions with the index they had in `fiction_state.items`, last one on top
+    deleted: Vec<(usize, Fiction)>,
+    /// digits typed before an action, like the 50 in `50%`
+    count: Option<usize>,
+    error: Option<String>,
 }
 
 impl App {
     pub fn new() -> Result<App> {
-        let path = dirs::config_dir()
-            .expect("failed to find config_dir")
-            .join("royal_rust")
-            .join("fictions.txt");
-        let client = RoyalClient::new();
-        let fiction_vec = Fiction::from_file(&client, &path).unwrap_or(Vec::new());
-        let app = App {
+        let path = config_dir().join("fictions.txt");
+        let (mut config, mut errors) = match Config::load() {
+            Ok(config) => (config, Vec::new()),
+            Err(e) => (Config::default(), vec![e]),
+        };
+        let history = History::load().unwrap_or_else(|e| {
+            errors.push(e);
+            History::default()
+        });
+        let shelves = Shelves::load().unwrap_or_else(|e| {
+            errors.push(e);
+            Shelves::default()
+        });
+        let shelf = config
+            .fictions
+            .shelf
+            .as_ref()
+            .and_then(|name| shelves.names.iter().position(|n| n == name));
+        let (keymap, keymap_errors) = Keymap::new(&config.keys);
+        errors.extend(keymap_errors);
+        let theme = match &config.theme {
+            Some(name) => Theme::resolve(name, &config.themes).unwrap_or_else(|e| {
+                errors.push(e);
+                Theme::default()
+            }),
+            None => Theme::default(),
+        };
+        let mut client = RoyalClient::new();
+        if let Some(dir) = ResponseCache::default_dir() {
+            client = client.with_cache(ResponseCache::with_policy(dir, config.cache.policy()));
+        }
+        let ids = match Fiction::read_ids(&path) {
+            Ok(ids) => ids,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                errors.push(format!("failed to read fictions.txt: {}", e));
+                Vec::new()
+            }
+        };
+        let client = Arc::new(client);
+        config.layout.sidebar_width = config.layout.sidebar_width.clamp(
+            LayoutConfig::MIN_SIDEBAR_WIDTH,
+            LayoutConfig::MAX_SIDEBAR_WIDTH,
+        );
+        let mut reading_state = ReadingWindowState::new(Vec::new(), 0, false);
+        reading_state.set_typography(config.typography);
+        let mut fiction_state = ListState::new(Vec::new(), 0, 0);
+        fiction_state.reversed = config.fictions.reversed;
+        let mut chapter_state = ListState::new(Vec::new(), 0, 0);
+        chapter_state.reversed = config.chapters.reversed;
+        let mut app = App {
+            prefetcher: Prefetcher::new(Arc::clone(&client)),
+            searcher: Searcher::new(Arc::clone(&client)),
+            loader: Loader::new(Arc::clone(&client), ids.clone()),
+            listed: ids
+                .iter()
+                .enumerate()
+                .map(|(line, id)| (*id, line))
+                .collect(),
+            unloaded: ids,
             client,
-            reading_state: ReadingWindowState::default(),
-            fiction_state: ListState::new(fiction_vec, 0, 0),
-            chapter_state: ListState::new(Vec::new(), 0, 0),
+            keymap,
+            theme,
+            reading_state,
+            fiction_state,
+            chapter_state,
             fictions_showing: true,
-            fiction_in: None,
+            fullscreen: false,
+            sidebar_area: Rect::default(),
+            reading_area: Rect::default(),
+            last_click: None,
+            resizing: false,
+            chapters_fiction: None,
+            chapters_new_since: None,
+            history,
+            shelves,
+            shelf,
+            reading: None,
+            search: None,
+            fulltext: None,
+            typography: None,
+            help: None,
+            indexes: HashMap::new(),
+            dirty_indexes: HashSet::new(),
+            index_dir: FictionIndex::default_dir(),
+            chapter_search: None,
+            search_ignore_case: config.reading.search_ignore_case,
+            search_regex: config.reading.search_regex,
+            sidebar_filter: None,
+            go_to: None,
+            tags: None,
+            shelf_name: None,
+            confirm_delete: None,
+            deleted: Vec::new(),
+            count: None,
+            error: (!errors.is_empty()).then(|| errors.join("; ")),
+            config,
         };
+        app.sort_fictions();
         Ok(app)
     }
 
     pub fn run(&mut self) -> Result<()> {
         stdout().execute(EnterAlternateScreen)?;
+        stdout().execute(EnableBracketedPaste)?;
+        stdout().execute(EnableMouseCapture)?;
         enable_raw_mode()?;
         let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
         loop {
             terminal.draw(|frame| self.draw(frame))?;
             if event::poll(std::time::Duration::from_millis(16))? {
-                if let event::Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press && self.handle_key(key) {
+                match event::read()? {
+                    event::Event::Key(key)
+                        if key.kind == KeyEventKind::Press && self.handle_key(key) =>
+                    {
                         break;
                     }
+                    event::Event::Mouse(mouse) => self.handle_mouse(mouse),
+                    event::Event::Paste(text) => self.paste(&text),
+                    _ => {}
                 }
             }
+            self.tick();
         }
+        let saved = self.save();
         terminal.clear()?;
+        stdout().execute(DisableMouseCapture)?;
+        stdout().execute(DisableBracketedPaste)?;
         stdout().execute(LeaveAlternateScreen)?;
         disable_raw_mode()?;
-        Ok(())
+        // reported only now, a message printed in the alternate screen would be lost
+        saved.map_err(std::io::Error::other)
+    }
+
+    /// Saves the library, history and shelves on quit, trying each even if another fails.
+    fn save(&mut self) -> std::result::Result<(), String> {
+        let mut errors = Vec::new();
+        // the fictions that did not load keep their place in the file
+        let mut ids = self
+            .fiction_state
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (self.added_rank(f.id, i), f.id))
+            .chain(
+                self.unloaded
+                    .iter()
+                    .filter(|id| self.fiction_by_id(**id).is_none())
+                    .map(|id| (self.added_rank(*id, 0), *id)),
+            )
+            .collect::<Vec<_>>();
+        ids.sort();
+        let ids = ids.into_iter().map(|(_, id)| id).collect::<Vec<_>>();
+        let path = config_dir().join("fictions.txt");
+        if let Err(e) = Fiction::write_ids(&path, &ids) {
+            errors.push(format!("failed to save fictions: {}", e));
+        }
+        if let Err(e) = self.history.save() {
+            errors.push(format!("failed to save history: {}", e));
+        }
+        // only fictions deleted for good, one that failed to load keeps its shelf and tags
+        for (_, fiction) in &self.deleted {
+            if self.fiction_by_id(fiction.id).is_none() {
+                self.shelves.forget(fiction.id);
+            }
+        }
+        if let Err(e) = self.shelves.save() {
+            errors.push(format!("failed to save shelves: {}", e));
+        }
+        self.save_indexes();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
     }
 
     fn draw(&mut self, frame: &mut Frame) {
+        let footer = !self.fullscreen
+            || self.chapter_search.is_some()
+            || self.sidebar_filter.is_some()
+            || self.go_to.is_some()
+            || self.tags.is_some()
+            || self.shelf_name.is_some()
+            || self.error.is_some()
+            || self.count.is_some();
         let master_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Min(0), Constraint::Length(1)])
+            .constraints(vec![Constraint::Min(0), Constraint::Length(footer as u16)])
             .split(frame.size());
-        frame.render_widget(
-            Paragraph::new(CONTROLS).left_aligned().style(Color::White),
-            master_layout[1],
-        );
+        if footer {
+            self.draw_footer(frame, master_layout[1]);
+        }
+        if self.fullscreen {
+            self.sidebar_area = Rect::default();
+            self.reading_area = master_layout[0];
+            frame.render_stateful_widget(
+                ReadingWindow::new((3, 1), self.theme),
+                master_layout[0],
+                &mut self.reading_state,
+            );
+        } else {
+            self.draw_panes(frame, master_layout[0]);
+        }
+        self.draw_popups(frame);
+    }
+
+    /// the sidebar and the reading window with its title
+    fn draw_panes(&mut self, frame: &mut Frame, area: Rect) {
         let layout = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(vec![Constraint::Percentage(30), Constraint::Percentage(70)])
-            .split(master_layout[0]);
+            .constraints(vec![
+                Constraint::Percentage(self.config.layout.sidebar_width),
+                Constraint::Min(0),
+            ])
+            .split(area);
+        (self.sidebar_area, self.reading_area) = (layout[0], layout[1]);
+        let (mut title, filter, shown, total) = if self.fictions_showing {
+            let state = &self.fiction_state;
+            (
+                format!("by {}", self.config.fictions.sort.name()),
+                state.filter(),
+                state.shown(),
+                state.items.len(),
+            )
+        } else {
+            let state = &self.chapter_state;
+            (
+                format!("Chapters by {}", self.config.chapters.sort.name()),
+                state.filter(),
+                state.shown(),
+                state.items.len(),
+            )
+        };
+        if !filter.is_empty() {
+            title = format!("{} · \"{}\" {}/{}", title, filter, shown, total);
+        }
+        if self.fictions_showing && self.loader.remaining() > 0 {
+            title = format!("{} · loading {}", title, self.loader.remaining());
+        }
         let title = if self.fictions_showing {
-            "Fictions"
+            let room = (layout[0].width as usize).saturating_sub(2 + text::width(&title));
+            let mut spans = self.shelf_switcher(room);
+            spans.push(Span::raw(title));
+            Line::from(spans)
         } else {
-            "Chapters"
+            Line::raw(title)
         };
         frame.render_widget(
             Block::new()
                 .title(title)
-                .style(Color::Blue)
+                .style(self.theme.border)
                 .borders(Borders::ALL),
             layout[0],
         );
         if self.fictions_showing {
+            let context = FictionContext {
+                history: &self.history,
+                shelves: &self.shelves,
+                expanded: self.config.fictions.expanded,
+                now: now(),
+            };
             frame.render_stateful_widget(
-                ListWidget::new((1, 1)),
+                ListWidget::new(SIDEBAR_MARGIN, self.theme, &context),
                 layout[0],
                 &mut self.fiction_state,
             );
         } else {
+            let context = ChapterContext {
+                history: &self.history,
+                config: &self.config.chapters,
+                new_since: self.chapters_new_since,
+                now: now(),
+                total: self.chapter_state.items.len(),
+            };
             frame.render_stateful_widget(
-                ListWidget::new((1, 1)),
+                ListWidget::new(SIDEBAR_MARGIN, self.theme, &context),
                 layout[0],
                 &mut self.chapter_state,
             );
         }
 
-        let title = if self.reading_state.is_reading {
-            format!(
-                " {} - {} ",
-                self.fiction_state.items[self.get_fiction_ind()].title,
-                self.chapter_state.items[self.get_chapter_ind()].title,
-            )
-        } else {
-            String::new()
+        let title = match self.reading.and_then(|r| {
+            let fiction = self.fiction_by_id(r.fiction)?;
+            Some((fiction, fiction.chapters.get(r.chapter)?))
+        }) {
+            Some((fiction, chapter)) => format!(" {} - {} ", fiction.title, chapter.title),
+            None => String::new(),
         };
         frame.render_widget(
             Block::new()
                 .title(title)
-                .style(Color::Blue)
+                .style(self.theme.border)
                 .borders(Borders::ALL),
             layout[1],
         );
 
         frame.render_stateful_widget(
-            ReadingWindow::new((3, 2)),
+            ReadingWindow::new((3, 2), self.theme),
             layout[1],
             &mut self.reading_state,
         );
+    }
 
-        if self.fiction_in.is_some() {
-            let x = frame.size().width / 2 - 35;
-            let y = frame.size().height / 2 - 2;
-            Block::new()
-                .title("Fiction Input")
-                .style(Style::default().bg(Color::Reset).fg(Color::White))
-                .borders(Borders::ALL)
-                .render(
-                    Rect {
-                        width: 70,
-                        height: 4,
-                        x,
-                        y,
-                    },
-                    frame.buffer_mut(),
-                );
-            frame.buffer_mut().set_line(
-                x + 2,
-                y + 2,
-                &Line::styled(self.fiction_in.as_ref().unwrap(), Color::White),
-                17,
+    /// The shelf names for the sidebar title, the shown one highlighted. Names before it
+    /// are left out when they do not fit in `room` columns, so it stays in sight.
+    fn shelf_switcher(&self, room: usize) -> Vec<Span<'static>> {
+        let current = self.shelf.map_or(0, |s| s + 1);
+        let names = std::iter::once("All")
+            .chain(self.shelves.names.iter().map(String::as_str))
+            .map(|name| format!(" {} ", name))
+            .collect::<Vec<_>>();
+        let mut first = 0;
+        while first < current
+            && names[first..=current]
+                .iter()
+                .map(|n| text::width(n))
+                .sum::<usize>()
+                + 1
+                > room
+        {
+            first += 1;
+        }
+        let mut spans = Vec::new();
+        if first > 0 {
+            spans.push(Span::styled("…", self.theme.dim));
+        }
+        for (i, name) in names.into_iter().enumerate().skip(first) {
+            let style = if i == current {
+                self.theme.selection
+            } else {
+                self.theme.dim
+            };
+            spans.push(Span::styled(name, style));
+        }
+        spans.push(Span::raw(" "));
+        spans
+    }
+
+    fn draw_popups(&mut self, frame: &mut Frame) {
+        if let Some(search) = &mut self.search {
+            let size = frame.size();
+            let width = size.width.min(100).max(size.width * 4 / 5);
+            let height = size.height.min(30).max(size.height * 4 / 5);
+            frame.render_stateful_widget(
+                SearchWidget::new(self.theme),
+                Rect {
+                    x: (size.width - width) / 2,
+                    y: (size.height - height) / 2,
+                    width,
+                    height,
+                },
+                search,
+            );
+        }
+        if let Some(typography) = &mut self.typography {
+            let size = frame.size();
+            let width = size.width.min(44);
+            let height = size.height.min(11);
+            frame.render_stateful_widget(
+                TypographyWidget::new(self.theme),
+                Rect {
+                    x: (size.width - width) / 2,
+                    y: (size.height - height) / 2,
+                    width,
+                    height,
+                },
+                typography,
+            );
+        }
+        if let Some(fiction) = self.confirm_delete.and_then(|id| self.fiction_by_id(id)) {
+            let question = format!("Delete {}?", fiction.title);
+            let size = frame.size();
+            let width = ConfirmWidget::width(&question, size.width);
+            let height = size.height.min(5);
+            frame.render_widget(
+                ConfirmWidget::new(&question, self.theme),
+                Rect {
+                    x: (size.width - width) / 2,
+                    y: (size.height - height) / 2,
+                    width,
+                    height,
+                },
+            );
+        }
+        if let Some(fulltext) = &mut self.fulltext {
+            let size = frame.size();
+            let width = size.width.min(100).max(size.width * 4 / 5);
+            let height = size.height.min(30).max(size.height * 4 / 5);
+            frame.render_stateful_widget(
+                FullTextWidget::new(self.theme),
+                Rect {
+                    x: (size.width - width) / 2,
+                    y: (size.height - height) / 2,
+                    width,
+                    height,
+                },
+                fulltext,
             );
         }
+        if let Some(help) = &mut self.help {
+            let size = frame.size();
+            let width = size.width.min(70).max(size.width * 3 / 5);
+            let height = size.height.min(40).max(size.height * 4 / 5);
+            frame.render_stateful_widget(
+                HelpWidget::new(&self.keymap, self.theme),
+                Rect {
+                    x: (size.width - width) / 2,
+                    y: (size.height - height) / 2,
+                    width,
+                    height,
+                },
+                help,
+            );
+        }
+    }
+
+    fn draw_footer(&mut self, frame: &mut Frame, area: Rect) {
+        let theme = self.theme;
+        if let Some(prompt) = &mut self.sidebar_filter {
+            let (shown, total) = if self.fictions_showing {
+                (self.fiction_state.shown(), self.fiction_state.items.len())
+            } else {
+                (self.chapter_state.shown(), self.chapter_state.items.len())
+            };
+            let status = format!("{} of {} (enter keeps, esc clears)", shown, total);
+            draw_prompt(frame, area, theme, " filter: ", &status, prompt);
+            return;
+        }
+        if let Some((_, prompt)) = &mut self.tags {
+            draw_prompt(frame, area, theme, " tags: ", "comma separated", prompt);
+            return;
+        }
+        if let Some((shelf, prompt)) = &mut self.shelf_name {
+            let label = match shelf {
+                Some(_) => " rename shelf: ",
+                None => " new shelf: ",
+            };
+            draw_prompt(frame, area, theme, label, "", prompt);
+            return;
+        }
+        if let Some(prompt) = &mut self.go_to {
+            draw_prompt(
+                frame,
+                area,
+                theme,
+                " go to: ",
+                "number, +n, -n or title",
+                prompt,
+            );
+            return;
+        }
+        if let Some(prompt) = &mut self.chapter_search {
+            let flags = format!(
+                "{}, {} (ctrl-t, ctrl-r)",
+                if self.search_ignore_case {
+                    "ignore case"
+                } else {
+                    "match case"
+                },
+                if self.search_regex { "regex" } else { "plain" },
+            );
+            draw_prompt(frame, area, theme, " /", &flags, prompt);
+            return;
+        }
+        let mut matches = match self.reading_state.match_position() {
+            Some((current, total)) => format!(" [{}/{}]", current, total),
+            None => String::new(),
+        };
+        if let Some((page, pages)) = self.reading_state.page_position() {
+            matches.insert_str(0, &format!(" page {} of {}", page, pages));
+        }
+        let footer = match (&self.error, self.count) {
+            (Some(e), _) => Paragraph::new(format!(" {}", e)).style(self.theme.error),
+            (None, Some(count)) => Paragraph::new(format!(" {}", count)).style(self.theme.text),
+            (None, None) => {
+                let hints = self.keymap.describe(&self.footer_hints());
+                Paragraph::new(format!("{} {}", matches, hints)).style(self.theme.text)
+            }
+        };
+        frame.render_widget(footer.left_aligned(), area);
+    }
+
+    /// work that runs between input events, like sending a debounced search
+    fn tick(&mut self) {
+        let mut loaded = false;
+        let mut failed = Vec::new();
+        for (id, fiction) in self.loader.poll() {
+            match fiction {
+                Ok(fiction) => {
+                    self.unloaded.retain(|x| *x != id);
+                    // unless it was added from the search in the meantime
+                    if self.fiction_by_id(id).is_none() {
+                        self.fiction_state.items.push(fiction);
+                        loaded = true;
+                    }
+                }
+                Err(e) => failed.push(format!("failed to load fiction {}: {}", id, e)),
+            }
+        }
+        if loaded {
+            self.sort_fictions();
+        }
+        if !failed.is_empty() {
+            self.error = Some(failed.join("; "));
+        }
+        for (fiction, chapter) in self.prefetcher.poll() {
+            self.history.record(&chapter);
+            self.index_chapter(fiction, &chapter);
+        }
+        let finished = self.searcher.poll();
+        let preview = self.searcher.poll_preview();
+        if let Some(search) = &mut self.search {
+            if let Some((query, results)) = finished {
+                search.set_results(&query, results);
+            }
+            if let Some((id, fiction)) = preview {
+                search.set_preview(id, fiction);
+            }
+            if let Some(query) = search.pending_query() {
+                search.searching(query.clone());
+                self.searcher.request(query);
+            }
+        }
+    }
+
+    /// Types pasted text into the prompt that has the keyboard, the one `handle_key` would
+    /// give a key to.
+    fn paste(&mut self, text: &str) {
+        self.error = None;
+        if let Some(prompt) = &mut self.chapter_search {
+            prompt.insert_str(text);
+        } else if self.confirm_delete.is_some() {
+            // only a key answers the question
+        } else if let Some((_, prompt)) = &mut self.tags {
+            prompt.insert_str(text);
+        } else if let Some((_, prompt)) = &mut self.shelf_name {
+            prompt.insert_str(text);
+        } else if let Some(prompt) = &mut self.go_to {
+            prompt.insert_str(text);
+        } else if let Some(prompt) = &mut self.sidebar_filter {
+            prompt.insert_str(text);
+            let filter = prompt.text.clone();
+            self.set_sidebar_filter(&filter);
+        } else if self.help.is_some() || self.typography.is_some() {
+            // nothing to type into
+        } else if let Some(fulltext) = &mut self.fulltext {
+            fulltext.input.insert_str(text);
+            self.update_fulltext();
+        } else if let Some(search) = &mut self.search {
+            if search.preview.is_none() {
+                search.input.insert_str(text);
+                search.edited();
+            }
+        }
     }
 
     fn handle_key(&mut self, key: KeyEvent) -> bool {
-        if key.modifiers.contains(KeyModifiers::SHIFT) {
+        self.error = None;
+        if let Some(prompt) = &mut self.chapter_search {
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
             match key.code {
-                KeyCode::Char('J') => {
-                    if self.fictions_showing {
-                        self.fiction_state.selected_line += 1;
+                KeyCode::Esc => self.chapter_search = None,
+                KeyCode::Char('t') if ctrl => self.search_ignore_case = !self.search_ignore_case,
+                KeyCode::Char('r') if ctrl => self.search_regex = !self.search_regex,
+                KeyCode::Enter => {
+                    if let Err(e) = self.confirm_chapter_search() {
+                        self.chapter_search.as_mut().unwrap().error = Some(e);
                     } else {
-                        self.chapter_state.selected_line += 1;
+                        self.chapter_search = None;
                     }
                 }
-                KeyCode::Char('K') => {
-                    if self.fictions_showing {
-                        // prevent underflow
-                        self.fiction_state.selected_line =
-                            self.fiction_state.selected_line.checked_sub(1).unwrap_or(0);
-                    } else {
-                        self.chapter_state.selected_line =
-                            self.chapter_state.selected_line.checked_sub(1).unwrap_or(0);
+                _ => {
+                    prompt.handle_key(key);
+                }
+            }
+        } else if let Some(id) = self.confirm_delete {
+            self.confirm_delete = None;
+            if let KeyCode::Char('y') | KeyCode::Enter = key.code {
+                self.delete_fiction(id);
+            }
+        } else if let Some((id, prompt)) = &mut self.tags {
+            match key.code {
+                KeyCode::Esc => self.tags = None,
+                KeyCode::Enter => {
+                    self.shelves.set_tags(*id, &prompt.text);
+                    self.tags = None;
+                    self.sort_fictions();
+                    if let Err(e) = self.shelves.save() {
+                        self.error = Some(format!("failed to save shelves: {}", e));
                     }
                 }
-                _ => {}
+                _ => {
+                    prompt.handle_key(key);
+                }
             }
-        } else if self.fiction_in.is_some() {
+        } else if let Some((shelf, prompt)) = &mut self.shelf_name {
             match key.code {
-                KeyCode::Char(c) => {
-                    if c.is_digit(10) {
-                        if self
-                            .fiction_in
-                            .as_ref()
-                            .unwrap()
-                            .chars()
-                            .all(|c| c.is_ascii_digit())
-                        {
-                            self.fiction_in.as_mut().unwrap().push(c);
-                        } else {
-                            // nothing input yet, default message
-                            *self.fiction_in.as_mut().unwrap() = String::from(c);
+                KeyCode::Esc => self.shelf_name = None,
+                KeyCode::Enter => {
+                    let (shelf, name) = (*shelf, prompt.text.clone());
+                    if let Err(e) = self.name_shelf(shelf, &name) {
+                        if let Some((_, prompt)) = &mut self.shelf_name {
+                            prompt.error = Some(e);
                         }
+                    } else {
+                        self.shelf_name = None;
+                    }
+                }
+                _ => {
+                    prompt.handle_key(key);
+                }
+            }
+        } else if let Some(prompt) = &mut self.go_to {
+            match key.code {
+                KeyCode::Esc => self.go_to = None,
+                KeyCode::Enter => {
+                    if let Err(e) = self.confirm_go_to() {
+                        self.go_to.as_mut().unwrap().error = Some(e);
+                    } else {
+                        self.go_to = None;
                     }
                 }
+                _ => {
+                    prompt.handle_key(key);
+                }
+            }
+        } else if let Some(prompt) = &mut self.sidebar_filter {
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
                 KeyCode::Esc => {
-                    self.fiction_in = None;
+                    self.sidebar_filter = None;
+                    self.set_sidebar_filter("");
                 }
-                KeyCode::Backspace => {
-                    self.fiction_in.as_mut().unwrap().pop();
+                KeyCode::Enter => self.sidebar_filter = None,
+                KeyCode::Down | KeyCode::Tab => self.move_sidebar(true),
+                KeyCode::Char('n') if ctrl => self.move_sidebar(true),
+                KeyCode::Up | KeyCode::BackTab => self.move_sidebar(false),
+                KeyCode::Char('p') if ctrl => self.move_sidebar(false),
+                _ => {
+                    if prompt.handle_key(key) {
+                        let filter = prompt.text.clone();
+                        self.set_sidebar_filter(&filter);
+                    }
                 }
-                KeyCode::Enter => {
-                    match self
-                        .client
-                        .get_fiction(self.fiction_in.as_ref().unwrap().parse::<usize>().unwrap())
-                    {
-                        Some(x) => {
-                            self.fiction_in = None;
-                            self.fiction_state.items.push(x);
-                        }
-                        None => {
-                            self.fiction_in = Some(String::from("Invalid ID"));
-                        }
+            }
+        } else if let Some(help) = &mut self.help {
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc | KeyCode::Char('q' | '?') => self.help = None,
+                KeyCode::Down | KeyCode::Char('j') => help.scroll_down(1),
+                KeyCode::Up | KeyCode::Char('k') => help.scroll_up(1),
+                KeyCode::Char('f' | 'd') if ctrl => help.page_down(),
+                KeyCode::Char('b' | 'u') if ctrl => help.page_up(),
+                KeyCode::PageDown | KeyCode::Char(' ') => help.page_down(),
+                KeyCode::PageUp | KeyCode::Char('b') => help.page_up(),
+                KeyCode::Home | KeyCode::Char('g') => help.scroll_up(usize::MAX),
+                KeyCode::End | KeyCode::Char('G') => help.scroll_down(usize::MAX),
+                _ => {}
+            }
+        } else if let Some(typography) = &mut self.typography {
+            match key.code {
+                KeyCode::Esc => {
+                    self.reading_state.set_typography(typography.original);
+                    self.typography = None;
+                }
+                KeyCode::Enter | KeyCode::Char('q') => {
+                    self.config.typography = typography.typography;
+                    self.typography = None;
+                    if let Err(e) = Config::save_section("typography", &self.config.typography) {
+                        self.error = Some(e);
                     }
                 }
+                KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => typography.select_next(),
+                KeyCode::Up | KeyCode::Char('k') | KeyCode::BackTab => typography.select_prev(),
+                KeyCode::Right | KeyCode::Char('l' | '+' | ' ') => typography.adjust(true),
+                KeyCode::Left | KeyCode::Char('h' | '-') => typography.adjust(false),
                 _ => {}
             }
-        } else {
+            if let Some(typography) = &self.typography {
+                self.reading_state.set_typography(typography.typography);
+            }
+        } else if let Some(fulltext) = &mut self.fulltext {
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => self.fulltext = None,
+                KeyCode::Down | KeyCode::Tab => fulltext.select_next(),
+                KeyCode::Char('n') if ctrl => fulltext.select_next(),
+                KeyCode::Up | KeyCode::BackTab => fulltext.select_prev(),
+                KeyCode::Char('p') if ctrl => fulltext.select_prev(),
+                KeyCode::Enter => match self.open_fulltext_hit() {
+                    Ok(()) => self.fulltext = None,
+                    Err(e) => self.fulltext.as_mut().unwrap().input.error = Some(e),
+                },
+                _ => {
+                    if fulltext.input.handle_key(key) {
+                        self.update_fulltext();
+                    }
+                }
+            }
+        } else if let Some(search) = &mut self.search {
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
             match key.code {
-                KeyCode::Char('q') => {
-                    let path = dirs::config_dir()
-                        .expect("failed to find config_dir")
-                        .join("royal_rust")
-                        .join("fictions.txt");
-                    Fiction::write_to_file(&path, &self.fiction_state.items)
-                        .expect("failed to save fictions");
-                    return true;
-                }
-                KeyCode::Char('j') => {
-                    self.reading_state.line += 1;
-                }
-                KeyCode::Char('k') => {
-                    // overflow mega sadge
-                    self.reading_state.line = self.reading_state.line.checked_sub(1).unwrap_or(0);
-                }
-                KeyCode::Char('d') => {
-                    self.fiction_state.items.remove(self.get_item_ind());
-                }
-                KeyCode::Char('l') => {
-                    let item_ind = self.get_item_ind();
-                    if self.fictions_showing && !self.fiction_state.items.is_empty() {
-                        self.chapter_state.selected_line = 0;
-                        self.fictions_showing = false;
-                        self.chapter_state.items =
-                            self.fiction_state.items[item_ind].chapters.clone();
-                    } else if !self.fictions_showing && !self.chapter_state.items.is_empty() {
-                        self.reading_state.line = 0;
-                        self.reading_state.is_reading = true;
-                        self.reading_state.text = Chapter::from_reference(
-                            &self.chapter_state.items[item_ind],
-                            &self.client,
-                        )
-                        .unwrap()
-                        .content;
+                KeyCode::Esc if search.preview.is_some() || search.loading.is_some() => {
+                    search.preview = None;
+                    search.loading = None;
+                }
+                KeyCode::Esc => self.search = None,
+                KeyCode::Down | KeyCode::Tab => search.select_next(),
+                KeyCode::Char('n') if ctrl => search.select_next(),
+                KeyCode::Up | KeyCode::BackTab => search.select_prev(),
+                KeyCode::Char('p') if ctrl => search.select_prev(),
+                KeyCode::Enter => {
+                    if let Err(e) = self.confirm_search() {
+                        self.search.as_mut().unwrap().input.error = Some(e);
+                    }
+                }
+                _ if search.preview.is_none() && search.input.handle_key(key) => {
+                    search.edited();
+                }
+                _ => {}
+            }
+        } else if let (KeyCode::Char(c @ '0'..='9'), None) = (key.code, self.keymap.action(key)) {
+            let digit = c.to_digit(10).unwrap() as usize;
+            self.count = Some(self.count.unwrap_or(0).saturating_mul(10) + digit);
+            return false;
+        } else if let Some(action) = self.keymap.action(key) {
+            let count = self.count.take();
+            match action {
+                Action::Quit => return true,
+                Action::SearchFiction => {
+                    let fiction = self
+                        .reading
+                        .map(|r| r.fiction)
+                        .or(self.chapters_fiction)
+                        .or_else(|| self.fiction_state.selected_item().map(|f| f.id));
+                    match fiction.and_then(|id| self.fiction_by_id(id)) {
+                        Some(fiction) => {
+                            let mut state = FullTextState::new(fiction.id, fiction.title.clone());
+                            state.update(self.index(state.fiction));
+                            self.fulltext = Some(state);
+                        }
+                        None => self.error = Some(String::from("no fiction selected")),
+                    }
+                }
+                Action::Typography => {
+                    self.typography = Some(TypographyState::new(self.reading_state.typography()));
+                }
+                Action::TogglePaged => {
+                    let mut typography = self.reading_state.typography();
+                    typography.paged = !typography.paged;
+                    self.reading_state.set_typography(typography);
+                    self.config.typography = typography;
+                    if let Err(e) = Config::save_section("typography", &typography) {
+                        self.error = Some(e);
+                    }
+                }
+                Action::Fullscreen => self.fullscreen = !self.fullscreen,
+                Action::Help => self.help = Some(HelpState::default()),
+                Action::SidebarWider | Action::SidebarNarrower => {
+                    let layout = &mut self.config.layout;
+                    layout.sidebar_width = if action == Action::SidebarWider {
+                        layout.sidebar_width + 5
+                    } else {
+                        layout.sidebar_width.saturating_sub(5)
+                    }
+                    .clamp(
+                        LayoutConfig::MIN_SIDEBAR_WIDTH,
+                        LayoutConfig::MAX_SIDEBAR_WIDTH,
+                    );
+                    self.fullscreen = false;
+                    if let Err(e) = Config::save_section("layout", &self.config.layout) {
+                        self.error = Some(e);
+                    }
+                }
+                Action::ToggleExpanded => {
+                    self.config.fictions.expanded = !self.config.fictions.expanded;
+                    if let Err(e) = Config::save_section("fictions", &self.config.fictions) {
+                        self.error = Some(e);
+                    }
+                }
+                Action::ScrollDown | Action::PageDown | Action::HalfPageDown
+                    if self.config.reading.auto_advance
+                        && self.reading.is_some()
+                        && self.reading_state.at_bottom() =>
+                {
+                    if let Err(e) = self.step_chapter(true, true) {
+                        self.error = Some(e);
+                    }
+                }
+                Action::ScrollDown => self.reading_state.scroll_down(count.unwrap_or(1)),
+                Action::ScrollUp => self.reading_state.scroll_up(count.unwrap_or(1)),
+                Action::PageDown => self.reading_state.page_down(),
+                Action::PageUp => self.reading_state.page_up(),
+                Action::HalfPageDown => self.reading_state.half_page_down(),
+                Action::HalfPageUp => self.reading_state.half_page_up(),
+                Action::Top => self.reading_state.top(),
+                Action::Bottom => self.reading_state.bottom(),
+                Action::JumpPercent => self.reading_state.jump_percent(count.unwrap_or(0)),
+                Action::SearchText => {
+                    let mut prompt = TextInput::default();
+                    if let Some(query) = self.reading_state.search_query() {
+                        prompt.insert_str(query);
+                    }
+                    self.chapter_search = Some(prompt);
+                }
+                Action::NextMatch | Action::PrevMatch => {
+                    if !self.reading_state.step_match(action == Action::NextMatch) {
+                        self.error = Some(String::from("no matches"));
+                    }
+                }
+                Action::ClearSearch => self.reading_state.set_search(None),
+                Action::NextChapter | Action::PrevChapter => {
+                    if let Err(e) = self.step_chapter(action == Action::NextChapter, false) {
+                        self.error = Some(e);
+                    }
+                }
+                Action::SidebarDown => self.move_sidebar(true),
+                Action::SidebarUp => self.move_sidebar(false),
+                Action::FilterSidebar => {
+                    let mut prompt = TextInput::default();
+                    prompt.insert_str(if self.fictions_showing {
+                        self.fiction_state.filter()
+                    } else {
+                        self.chapter_state.filter()
+                    });
+                    self.sidebar_filter = Some(prompt);
+                    self.fullscreen = false;
+                }
+                Action::GoToChapter if self.fictions_showing => {
+                    self.error = Some(String::from("open the chapter list to go to a chapter"));
+                }
+                Action::NextShelf | Action::PrevShelf => {
+                    // all of them, then each shelf
+                    let len = self.shelves.names.len() + 1;
+                    let current = self.shelf.map_or(0, |s| s + 1);
+                    let next = if action == Action::NextShelf {
+                        (current + 1) % len
+                    } else {
+                        (current + len - 1) % len
+                    };
+                    self.set_shelf(next.checked_sub(1));
+                }
+                Action::MoveToNextShelf | Action::MoveToPrevShelf => match self.current_fiction() {
+                    Some(id) => {
+                        let forward = action == Action::MoveToNextShelf;
+                        self.shelves.step_shelf(id, forward);
+                        // moved off the shelf shown, the selection goes to the next fiction
+                        let order = self.fiction_order();
+                        self.fiction_state.set_order_keeping_row(order);
+                        if let Err(e) = self.shelves.save() {
+                            self.error = Some(format!("failed to save shelves: {}", e));
+                        }
                     }
+                    None => self.error = Some(String::from("no fiction selected")),
+                },
+                Action::EditTags => match self.current_fiction() {
+                    Some(id) => {
+                        let mut prompt = TextInput::default();
+                        prompt.insert_str(&self.shelves.tags(id).join(", "));
+                        self.tags = Some((id, prompt));
+                        self.fullscreen = false;
+                    }
+                    None => self.error = Some(String::from("no fiction selected")),
+                },
+                Action::NewShelf => {
+                    self.shelf_name = Some((None, TextInput::default()));
+                    self.fullscreen = false;
+                }
+                Action::RenameShelf => match self.shelf {
+                    Some(shelf) => {
+                        let mut prompt = TextInput::default();
+                        prompt.insert_str(&self.shelves.names[shelf]);
+                        self.shelf_name = Some((Some(shelf), prompt));
+                        self.fullscreen = false;
+                    }
+                    None => self.error = Some(String::from("switch to a shelf to rename it")),
+                },
+                Action::ToggleVolumes if !self.fictions_showing => {
+                    self.chapter_state.toggle_all_groups();
+                }
+                Action::ToggleVolumes => {}
+                Action::GoToChapter => {
+                    self.go_to = Some(TextInput::default());
+                    self.fullscreen = false;
+                }
+                Action::Delete if !self.fictions_showing => {
+                    self.error = Some(String::from("fictions are deleted from the fiction list"));
                 }
-                KeyCode::Char('h') => {
+                Action::Delete => match self.fiction_state.selected_item() {
+                    Some(fiction) => self.confirm_delete = Some(fiction.id),
+                    None => self.error = Some(String::from("no fiction selected")),
+                },
+                Action::Undo => match self.deleted.pop() {
+                    Some((ind, fiction)) => {
+                        // added again since, from the search
+                        let ind = match self
+                            .fiction_state
+                            .items
+                            .iter()
+                            .position(|f| f.id == fiction.id)
+                        {
+                            Some(existing) => existing,
+                            None => {
+                                let ind = ind.min(self.fiction_state.items.len());
+                                self.fiction_state.items.insert(ind, fiction);
+                                self.sort_fictions();
+                                ind
+                            }
+                        };
+                        self.select_fiction(ind);
+                    }
+                    None => self.error = Some(String::from("nothing to undo")),
+                },
+                Action::Select => self.select(),
+                Action::Back => {
                     self.fictions_showing = true;
                 }
-                KeyCode::Char('o') => {
+                Action::AddFiction => {
                     self.fictions_showing = true;
-                    self.fiction_in = Some(String::from("Enter New Fiction ID"));
+                    self.search = Some(SearchState::new());
                 }
-                KeyCode::Char('r') => {
-                    if self.fictions_showing {
-                        self.fiction_state.reversed = !self.fiction_state.reversed;
+                Action::Reverse => {
+                    let saved = if self.fictions_showing {
+                        self.fiction_state.reverse();
+                        self.config.fictions.reversed = self.fiction_state.reversed;
+                        Config::save_section("fictions", &self.config.fictions)
                     } else {
-                        self.chapter_state.reversed = !self.chapter_state.reversed;
+                        self.chapter_state.reverse();
+                        self.config.chapters.reversed = self.chapter_state.reversed;
+                        Config::save_section("chapters", &self.config.chapters)
+                    };
+                    if let Err(e) = saved {
+                        self.error = Some(e);
+                    }
+                }
+                Action::CycleSort => {
+                    let saved = if self.fictions_showing {
+                        self.config.fictions.sort = self.config.fictions.sort.next();
+                        self.sort_fictions();
+                        Config::save_section("fictions", &self.config.fictions)
+                    } else {
+                        self.config.chapters.sort = self.config.chapters.sort.next();
+                        self.sort_chapters();
+                        Config::save_section("chapters", &self.config.chapters)
+                    };
+                    if let Err(e) = saved {
+                        self.error = Some(e);
                     }
                 }
-                _ => {}
             }
+        } else {
+            self.count = None;
         }
         false
     }
 
-    fn get_item_ind(&self) -> usize {
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let position = Position {
+            x: mouse.column,
+            y: mouse.row,
+        };
+        let over_sidebar = self.sidebar_area.contains(position);
+        let over_reading = self.reading_area.contains(position);
+        // the sidebar's right border, the reading pane's left one sits right next to it
+        let on_border = !self.fullscreen
+            && (mouse.column + 1 == self.sidebar_area.right()
+                || mouse.column == self.reading_area.x)
+            && (over_sidebar || over_reading);
+        match mouse.kind {
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let down = mouse.kind == MouseEventKind::ScrollDown;
+                if let Some(help) = &mut self.help {
+                    if down {
+                        help.scroll_down(WHEEL_LINES)
+                    } else {
+                        help.scroll_up(WHEEL_LINES)
+                    }
+                } else if let Some(search) = &mut self.search {
+                    if down {
+                        search.select_next()
+                    } else {
+                        search.select_prev()
+                    }
+                } else if let Some(fulltext) = &mut self.fulltext {
+                    if down {
+                        fulltext.select_next()
+                    } else {
+                        fulltext.select_prev()
+                    }
+                } else if over_sidebar {
+                    self.move_sidebar(down);
+                } else if over_reading {
+                    // a notch turns a page in paged mode, which scrolls by pages anyway
+                    let lines = if self.reading_state.typography().paged {
+                        1
+                    } else {
+                        WHEEL_LINES
+                    };
+                    if down {
+                        self.reading_state.scroll_down(lines);
+                    } else {
+                        self.reading_state.scroll_up(lines);
+                    }
+                }
+            }
+            _ if self.search.is_some()
+                || self.fulltext.is_some()
+                || self.typography.is_some()
+                || self.confirm_delete.is_some()
+                || self.help.is_some() => {}
+            MouseEventKind::Down(MouseButton::Left) if on_border => self.resizing = true,
+            MouseEventKind::Down(MouseButton::Left) if over_sidebar => {
+                let (area, row) = (self.sidebar_area, mouse.row);
+                let line = if self.fictions_showing {
+                    let row_height = Fiction::row_height(&self.fiction_context());
+                    self.fiction_state
+                        .line_at(area, SIDEBAR_MARGIN, row_height, row)
+                } else {
+                    self.chapter_state.line_at(area, SIDEBAR_MARGIN, 1, row)
+                };
+                let Some(line) = line else {
+                    return;
+                };
+                if self.fictions_showing {
+                    self.fiction_state.selected_line = line;
+                } else {
+                    self.chapter_state.selected_line = line;
+                }
+                let now = Instant::now();
+                let double = self
+                    .last_click
+                    .is_some_and(|(at, last)| last == line && now - at <= DOUBLE_CLICK);
+                self.last_click = Some((now, line));
+                if double {
+                    self.last_click = None;
+                    self.select();
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.resizing => {
+                let total = self.sidebar_area.width + self.reading_area.width;
+                if total > 0 {
+                    let column = (mouse.column + 1).saturating_sub(self.sidebar_area.x);
+                    self.config.layout.sidebar_width = (column as u32 * 100 / total as u32).clamp(
+                        LayoutConfig::MIN_SIDEBAR_WIDTH as u32,
+                        LayoutConfig::MAX_SIDEBAR_WIDTH as u32,
+                    ) as u16;
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) if self.resizing => {
+                self.resizing = false;
+                if let Err(e) = Config::save_section("layout", &self.config.layout) {
+                    self.error = Some(e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The few keys worth showing in the footer for what has the focus, labelled with
+    /// what they do there. Everything else is in the help.
+    fn footer_hints(&self) -> Vec<(Action, &'static str)> {
+        let mut hints = Vec::new();
+        let reading = self.reading.is_some();
+        if self.fullscreen {
+            hints.extend([
+                (Action::PageDown, "page"),
+                (Action::NextChapter, "next chapter"),
+                (Action::Fullscreen, "leave fullscreen"),
+            ]);
+        } else if self.fictions_showing {
+            hints.push((Action::Select, "chapters"));
+            if reading {
+                hints.push((Action::PageDown, "page"));
+            }
+            hints.extend([
+                (Action::AddFiction, "add"),
+                (Action::FilterSidebar, "filter"),
+            ]);
+            if self.deleted.is_empty() {
+                hints.push((Action::NextShelf, "shelf"));
+            } else {
+                hints.push((Action::Undo, "undo delete"));
+            }
+        } else {
+            hints.extend([
+                (Action::Select, "read"),
+                (Action::Back, "fictions"),
+                (Action::GoToChapter, "go to"),
+            ]);
+            if reading {
+                hints.extend([
+                    (Action::PageDown, "page"),
+                    (Action::NextChapter, "next chapter"),
+                ]);
+            } else {
+                hints.push((Action::FilterSidebar, "filter"));
+            }
+        }
+        if self.reading_state.match_position().is_some() {
+            hints.push((Action::NextMatch, "next match"));
+        } else if reading {
+            hints.push((Action::SearchText, "search"));
+        }
+        hints.extend([(Action::Help, "help"), (Action::Quit, "quit")]);
+        hints
+    }
+
+    /// moves the sidebar selection one row
+    fn move_sidebar(&mut self, down: bool) {
+        let line = if self.fictions_showing {
+            &mut self.fiction_state.selected_line
+        } else {
+            &mut self.chapter_state.selected_line
+        };
+        // the widget clamps it to the last row
+        *line = if down {
+            line.saturating_add(1)
+        } else {
+            line.saturating_sub(1)
+        };
+    }
+
+    fn set_sidebar_filter(&mut self, filter: &str) {
         if self.fictions_showing {
-            self.get_fiction_ind()
+            self.fiction_state.set_filter(filter);
+        } else {
+            self.chapter_state.set_filter(filter);
+        }
+    }
+
+    fn fiction_context(&self) -> FictionContext<'_> {
+        FictionContext {
+            history: &self.history,
+            shelves: &self.shelves,
+            expanded: self.config.fictions.expanded,
+            now: now(),
+        }
+    }
+
+    /// opens the selected fiction's chapters or chapter, or folds the selected volume
+    fn select(&mut self) {
+        if self.fictions_showing {
+            if let Some(fiction_ind) = self.fiction_state.selected() {
+                self.show_chapters(fiction_ind);
+            }
+        } else if let Some(chapter_ind) = self.chapter_state.selected() {
+            let fiction = self.chapters_fiction.unwrap();
+            if let Err(e) = self.open_chapter(fiction, chapter_ind, false) {
+                self.error = Some(e);
+            }
+        } else {
+            self.chapter_state.toggle_selected_group();
+        }
+    }
+
+    fn show_chapters(&mut self, fiction_ind: usize) {
+        let fiction = &self.fiction_state.items[fiction_ind];
+        self.fictions_showing = false;
+        self.chapters_fiction = Some(fiction.id);
+        self.chapters_new_since = self.history.seen(fiction.id);
+        self.history.set_seen(fiction.id, now());
+        let chapters = fiction.chapters.clone();
+        let groups = volume_groups(fiction, &self.history);
+        let id = fiction.id;
+        self.chapter_state.set_items(chapters);
+        self.chapter_state.set_groups(groups);
+        self.sort_chapters();
+        if let Some(reading) = self.reading.filter(|r| r.fiction == id) {
+            self.chapter_state.select(reading.chapter);
+        }
+    }
+
+    /// orders the fiction list by the configured sort mode
+    fn sort_fictions(&mut self) {
+        let tags = self
+            .fiction_state
+            .items
+            .iter()
+            .map(|f| self.shelves.tags(f.id).to_vec())
+            .collect();
+        self.fiction_state.set_tags(tags);
+        let order = self.fiction_order();
+        self.fiction_state.set_order(order);
+    }
+
+    /// the fictions on the shelf shown, in the configured sort order
+    fn fiction_order(&self) -> Vec<usize> {
+        let items = &self.fiction_state.items;
+        let mut order = (0..items.len()).collect::<Vec<_>>();
+        match self.config.fictions.sort {
+            FictionSort::Added => order.sort_by_key(|i| Reverse(self.added_rank(items[*i].id, *i))),
+            FictionSort::Title => order.sort_by_cached_key(|i| items[*i].title.to_lowercase()),
+            FictionSort::Updated => order.sort_by_key(|i| Reverse(last_update(&items[*i]))),
+            FictionSort::Unread => {
+                order.sort_by_key(|i| Reverse(unread(&items[*i], &self.history)))
+            }
+            FictionSort::Author => order.sort_by_cached_key(|i| {
+                (
+                    items[*i].author.to_lowercase(),
+                    items[*i].title.to_lowercase(),
+                )
+            }),
+        }
+        if let Some(shelf) = self.shelf {
+            order.retain(|i| self.shelves.shelf(items[*i].id) == shelf);
+        }
+        order
+    }
+
+    /// orders the chapter list by the configured sort mode
+    fn sort_chapters(&mut self) {
+        let items = &self.chapter_state.items;
+        let mut order = (0..items.len()).collect::<Vec<_>>();
+        match self.config.chapters.sort {
+            ChapterSort::Release => order.reverse(),
+            ChapterSort::Edited => order.sort_by_key(|i| {
+                let chapter = &items[*i];
+                let edited = self
+                    .history
+                    .info(&chapter.path)
+                    .map_or(0, |info| info.edited);
+                Reverse(edited.max(chapter.time))
+            }),
+        }
+        self.chapter_state.set_order(order);
+    }
+
+    /// removes the fiction for the rest of the session, or until it is undone
+    fn delete_fiction(&mut self, id: usize) {
+        let Some(ind) = self.fiction_state.items.iter().position(|f| f.id == id) else {
+            return;
+        };
+        let fiction = self.fiction_state.items.remove(ind);
+        self.deleted.push((ind, fiction));
+        if self.reading.is_some_and(|r| r.fiction == id) {
+            self.reading = None;
+            self.reading_state.is_reading = false;
+            self.reading_state.set_search(None);
+            self.reading_state.set_text(Vec::new());
+            self.prefetcher.retain(&[]);
+        }
+        if self.chapters_fiction == Some(id) {
+            self.chapters_fiction = None;
+            self.chapter_state.set_items(Vec::new());
+        }
+        self.sort_fictions();
+    }
+
+    /// Orders fictions by when they were added: those listed in `fictions.txt` by their
+    /// line, then the ones added since by `index` into `fiction_state.items`.
+    fn added_rank(&self, id: usize, index: usize) -> (usize, usize) {
+        (self.listed.get(&id).copied().unwrap_or(usize::MAX), index)
+    }
+
+    fn fiction_by_id(&self, id: usize) -> Option<&Fiction> {
+        self.fiction_state.items.iter().find(|f| f.id == id)
+    }
+
+    /// Loads chapter `chapter_ind` (release order) of fiction `fiction_id` into the reading
+    /// window. With `separator`, the text starts with a banner naming the chapter, for
+    /// continuous reading.
+    fn open_chapter(
+        &mut self,
+        fiction_id: usize,
+        chapter_ind: usize,
+        separator: bool,
+    ) -> std::result::Result<(), String> {
+        let reference = self
+            .fiction_by_id(fiction_id)
+            .and_then(|f| f.chapters.get(chapter_ind))
+            .ok_or_else(|| String::from("chapter not found"))?;
+        let chapter = match self.prefetcher.get(&reference.path) {
+            Some(chapter) => chapter.clone(),
+            None => Chapter::from_reference(reference, &self.client)
+                .map_err(|e| format!("failed to load chapter: {}", e))?,
+        };
+        self.index_chapter(fiction_id, &chapter);
+        self.history.record(&chapter);
+        self.history.mark_read(&chapter.path);
+        let mut text = chapter.content;
+        if separator {
+            text.insert(0, format!("───── {} ─────", chapter.name));
+        }
+        self.reading_state.is_reading = true;
+        self.reading_state.set_text(text);
+        self.reading = Some(Reading {
+            fiction: fiction_id,
+            chapter: chapter_ind,
+        });
+        self.prefetch();
+        // keep the sidebar on the open chapter, in whatever order it is displayed
+        if self.chapters_fiction == Some(fiction_id) {
+            self.chapter_state.select(chapter_ind);
+        }
+        // reading changes the unread counts, and with them maybe the order
+        self.sort_fictions();
+        Ok(())
+    }
+
+    /// Fetches the chapters following the open one in the background, and forgets
+    /// prefetched chapters that are no longer close to it.
+    fn prefetch(&mut self) {
+        let Some(reading) = self.reading else {
+            return;
+        };
+        let Some(fiction) = self.fiction_by_id(reading.fiction) else {
+            return;
+        };
+        let end =
+            (reading.chapter + 1 + self.config.reading.prefetch_depth).min(fiction.chapters.len());
+        let next = fiction.chapters[(reading.chapter + 1).min(end)..end].to_vec();
+        // the previous chapter is kept too, so stepping back and forth stays instant
+        let keep = fiction.chapters[reading.chapter.saturating_sub(1)..end]
+            .iter()
+            .map(|c| c.path.clone())
+            .collect::<Vec<_>>();
+        self.prefetcher.retain(&keep);
+        self.prefetcher.request(reading.fiction, &next);
+    }
+
+    /// Opens the chapter after (or before) the one being read. This is release order,
+    /// independent of how the sidebar is sorted.
+    fn step_chapter(&mut self, forward: bool, separator: bool) -> std::result::Result<(), String> {
+        let reading = self
+            .reading
+            .ok_or_else(|| String::from("no chapter open"))?;
+        let len = self
+            .fiction_by_id(reading.fiction)
+            .map_or(0, |f| f.chapters.len());
+        let chapter = if forward {
+            Some(reading.chapter + 1).filter(|c| *c < len)
+        } else {
+            reading.chapter.checked_sub(1)
+        }
+        .ok_or_else(|| {
+            String::from(if forward {
+                "this is the last chapter"
+            } else {
+                "this is the first chapter"
+            })
+        })?;
+        self.open_chapter(reading.fiction, chapter, separator)
+    }
+
+    /// the full-text index of `fiction`, loaded from disk the first time
+    fn index(&mut self, fiction: usize) -> &FictionIndex {
+        let dir = self.index_dir.clone();
+        self.indexes.entry(fiction).or_insert_with(|| {
+            dir.and_then(|dir| FictionIndex::load(&dir, fiction).ok())
+                .unwrap_or_else(|| FictionIndex::new(fiction))
+        })
+    }
+
+    /// reruns the full-text query against the index of its fiction
+    fn update_fulltext(&mut self) {
+        let Some(fiction) = self.fulltext.as_ref().map(|f| f.fiction) else {
+            return;
+        };
+        self.index(fiction);
+        if let Some(fulltext) = &mut self.fulltext {
+            fulltext.update(&self.indexes[&fiction]);
+        }
+    }
+
+    fn index_chapter(&mut self, fiction: usize, chapter: &Chapter) {
+        if self.index(fiction).is_current(chapter) {
+            return;
+        }
+        self.indexes.get_mut(&fiction).unwrap().add_chapter(chapter);
+        self.dirty_indexes.insert(fiction);
+    }
+
+    fn save_indexes(&mut self) {
+        let Some(dir) = &self.index_dir else {
+            return;
+        };
+        for fiction in self.dirty_indexes.drain() {
+            // losing the index is not worth failing to quit over, it refills as you read
+            let _ = self.indexes[&fiction].save(dir);
+        }
+    }
+
+    /// Enter in the full-text search: opens the chapter at the hit, with its terms highlighted
+    fn open_fulltext_hit(&mut self) -> std::result::Result<(), String> {
+        let fulltext = self.fulltext.as_ref().unwrap();
+        let fiction = fulltext.fiction;
+        let hit = fulltext
+            .selected_hit()
+            .ok_or_else(|| String::from("no hit selected"))?
+            .clone();
+        let terms = tokenize(&fulltext.input.text)
+            .map(|t| regex::escape(&t))
+            .collect::<Vec<_>>();
+        let chapter_ind = self
+            .fiction_by_id(fiction)
+            .and_then(|f| f.chapters.iter().position(|c| c.path == hit.chapter_path))
+            .ok_or_else(|| String::from("chapter is no longer listed for this fiction"))?;
+        self.open_chapter(fiction, chapter_ind, false)?;
+        self.reading_state.show_paragraph(hit.paragraph);
+        // the terms as a regex, which is what `/` gets to start from
+        let query = terms.join("|");
+        let regex = RegexBuilder::new(&query)
+            .case_insensitive(true)
+            .build()
+            .ok();
+        self.reading_state
+            .set_search(regex.map(|regex| (query, regex)));
+        Ok(())
+    }
+
+    /// Enter in the `:` prompt: a chapter number in release order, an offset from the
+    /// selected chapter like `+10` or `-3`, or part of a title, looked for after the
+    /// selected chapter so entering it again finds the next one
+    fn confirm_go_to(&mut self) -> std::result::Result<(), String> {
+        let query = self.go_to.as_ref().unwrap().text.trim().to_lowercase();
+        let chapters = &self.chapter_state.items;
+        let len = chapters.len();
+        if len == 0 {
+            return Err(String::from("no chapters"));
+        }
+        let current = self.chapter_state.selected().unwrap_or(0);
+        let offset = |sign: char| query.strip_prefix(sign)?.trim().parse::<usize>().ok();
+        let target = if let Some(n) = offset('+') {
+            current.saturating_add(n).min(len - 1)
+        } else if let Some(n) = offset('-') {
+            current.saturating_sub(n)
+        } else if let Ok(n) = query.parse::<usize>() {
+            if n == 0 || n > len {
+                return Err(format!("chapters are numbered 1 to {}", len));
+            }
+            n - 1
+        } else if query.is_empty() {
+            return Err(String::from("type a chapter number or title"));
         } else {
-            self.get_chapter_ind()
+            (1..=len)
+                .map(|k| (current + k) % len)
+                .find(|i| chapters[*i].title.to_lowercase().contains(&query))
+                .ok_or_else(|| format!("no chapter title contains \"{}\"", query))?
+        };
+        self.chapter_state.select(target);
+        // the filter may be hiding it
+        if self.chapter_state.selected() != Some(target) {
+            self.chapter_state.set_filter("");
+            self.chapter_state.select(target);
         }
+        self.chapter_state
+            .center_selected(self.sidebar_area, SIDEBAR_MARGIN, 1);
+        Ok(())
     }
 
-    fn get_chapter_ind(&self) -> usize {
-        if self.chapter_state.reversed {
-            self.chapter_state.items.len() - 1 - self.chapter_state.selected_line as usize
+    /// Enter in the `/` prompt: an empty query clears the search
+    fn confirm_chapter_search(&mut self) -> std::result::Result<(), String> {
+        let query = &self.chapter_search.as_ref().unwrap().text;
+        if query.is_empty() {
+            self.reading_state.set_search(None);
+            return Ok(());
+        }
+        let pattern = if self.search_regex {
+            query.clone()
         } else {
-            self.chapter_state.selected_line as usize
+            regex::escape(query)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(self.search_ignore_case)
+            .build()
+            .map_err(|e| match e {
+                regex::Error::Syntax(_) => String::from("invalid regex"),
+                e => e.to_string(),
+            })?;
+        self.reading_state.set_search(Some((query.clone(), regex)));
+        match self.reading_state.match_position() {
+            Some((_, 0)) => Err(String::from("no matches")),
+            _ => Ok(()),
         }
     }
 
-    fn get_fiction_ind(&self) -> usize {
-        if self.fiction_state.reversed {
-            self.fiction_state.items.len() - 1 - self.fiction_state.selected_line as usize
+    /// Enter in the search screen: open a typed link, preview the selected result,
+    /// or add the previewed fiction
+    fn confirm_search(&mut self) -> std::result::Result<(), String> {
+        let search = self.search.as_mut().unwrap();
+        if let Some(fiction) = search.preview.take() {
+            let fiction_ind = self.add_fiction(fiction);
+            self.select_fiction(fiction_ind);
+            self.search = None;
+        } else if let Some(link) = search.link() {
+            self.open_link(link)?;
+            self.search = None;
+        } else if let Some(result) = search.selected_result() {
+            let id = result.id;
+            search.loading = Some(id);
+            self.searcher.request_preview(id);
         } else {
-            self.fiction_state.selected_line as usize
+            search.input.error = Some(String::from("no fiction selected"));
+        }
+        Ok(())
+    }
+
+    /// pushes `fiction` unless it is already in the list, returns its index
+    fn add_fiction(&mut self, fiction: Fiction) -> usize {
+        match self
+            .fiction_state
+            .items
+            .iter()
+            .position(|f| f.id == fiction.id)
+        {
+            Some(i) => i,
+            None => {
+                // it goes on the shelf being looked at, the first one otherwise
+                if let Some(shelf) = self.shelf {
+                    self.shelves.set_shelf(fiction.id, shelf);
+                }
+                self.unloaded.retain(|id| *id != fiction.id);
+                self.fiction_state.items.push(fiction);
+                self.sort_fictions();
+                self.fiction_state.items.len() - 1
+            }
+        }
+    }
+
+    fn select_fiction(&mut self, fiction_ind: usize) {
+        self.fiction_state.select(fiction_ind);
+        // it is on another shelf
+        if self.fiction_state.selected() != Some(fiction_ind) {
+            self.set_shelf(None);
+            self.fiction_state.select(fiction_ind);
         }
+        self.fictions_showing = true;
     }
+
+    /// Enter in the shelf prompt: adds a shelf and shows it, or renames `shelf`
+    fn name_shelf(&mut self, shelf: Option<usize>, name: &str) -> std::result::Result<(), String> {
+        let shelf = match shelf {
+            Some(shelf) => {
+                self.shelves.rename(shelf, name)?;
+                shelf
+            }
+            None => self.shelves.add(name)?,
+        };
+        if let Err(e) = self.shelves.save() {
+            self.error = Some(format!("failed to save shelves: {}", e));
+        }
+        // saves the shown shelf under its new name too
+        self.set_shelf(Some(shelf));
+        Ok(())
+    }
+
+    /// shows the fictions on `shelf`, or all of them, and remembers it
+    fn set_shelf(&mut self, shelf: Option<usize>) {
+        self.shelf = shelf;
+        self.fictions_showing = true;
+        self.sort_fictions();
+        self.config.fictions.shelf = shelf.map(|s| self.shelves.names[s].clone());
+        if let Err(e) = Config::save_section("fictions", &self.config.fictions) {
+            self.error = Some(e);
+        }
+    }
+
+    /// the selected fiction, or the one whose chapters are shown
+    fn current_fiction(&self) -> Option<usize> {
+        if self.fictions_showing {
+            self.fiction_state.selected_item().map(|f| f.id)
+        } else {
+            self.chapters_fiction
+        }
+    }
+
+    /// adds the linked fiction if it is new, selects it, and opens the linked chapter if any
+    fn open_link(&mut self, link: FictionLink) -> std::result::Result<(), String> {
+        let fiction_id = match (link.fiction, link.chapter) {
+            (Some(id), _) => id,
+            (None, Some(chapter)) => self
+                .client
+                .fiction_of_chapter(chapter)
+                .map_err(|e| format!("failed to look up chapter: {}", e))?,
+            (None, None) => return Err(String::from("link does not point to a fiction")),
+        };
+        let fiction_ind = match self
+            .fiction_state
+            .items
+            .iter()
+            .position(|f| f.id == fiction_id)
+        {
+            Some(i) => i,
+            None => {
+                let fiction = self
+                    .client
+                    .get_fiction(fiction_id)
+                    .map_err(|e| format!("failed to load fiction: {}", e))?;
+                self.add_fiction(fiction)
+            }
+        };
+        self.select_fiction(fiction_ind);
+        let Some(chapter_id) = link.chapter else {
+            return Ok(());
+        };
+        self.show_chapters(fiction_ind);
+        let chapter_ind = self
+            .chapter_state
+            .items
+            .iter()
+            .position(|c| c.id == chapter_id)
+            .ok_or_else(|| String::from("chapter not found in this fiction"))?;
+        self.open_chapter(fiction_id, chapter_ind, false)
+    }
+}
+
+/// A one-line prompt: `label`, the input, and `status` on the right, or the input's error.
+fn draw_prompt(
+    frame: &mut Frame,
+    area: Rect,
+    theme: Theme,
+    label: &str,
+    status: &str,
+    prompt: &mut TextInput,
+) {
+    let (status, style) = match &prompt.error {
+        Some(e) => (e.as_str(), theme.error),
+        None => (status, theme.dim),
+    };
+    let status = text::truncate(status, (area.width / 2).saturating_sub(1) as usize, "…");
+    let status_width = (text::width(&status) as u16 + 1).min(area.width / 2);
+    frame.render_widget(
+        Paragraph::new(status).style(style).right_aligned(),
+        Rect {
+            x: area.x + area.width - status_width,
+            width: status_width,
+            ..area
+        },
+    );
+    let label_width = text::width(label) as u16;
+    frame.render_widget(Paragraph::new(label).style(theme.text), area);
+    frame.render_stateful_widget(
+        TextInputWidget::new(theme),
+        Rect {
+            x: area.x + label_width.min(area.width),
+            width: area.width.saturating_sub(label_width + status_width),
+            ..area
+        },
+        prompt,
+    );
 }
@@ -0,0 +1,81 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use royal_api::{Chapter, ChapterReference, RoyalClient, RoyalError};
+
+/// Fetches chapters on a background thread and keeps them in memory by path,
+/// so opening the next chapter does not wait on the network.
+pub struct Prefetcher {
+    client: Arc<RoyalClient>,
+    chapters: HashMap<String, Chapter>,
+    /// paths requested but not yet received
+    pending: HashSet<String>,
+    tx: Sender<(usize, Result<Chapter, RoyalError>, String)>,
+    rx: Receiver<(usize, Result<Chapter, RoyalError>, String)>,
+}
+
+impl Prefetcher {
+    pub fn new(client: Arc<RoyalClient>) -> Prefetcher {
+        let (tx, rx) = channel();
+        Self {
+            client,
+            chapters: HashMap::new(),
+            pending: HashSet::new(),
+            tx,
+            rx,
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Chapter> {
+        self.chapters.get(path)
+    }
+
+    /// Starts fetching every reference that is neither held nor already on its way.
+    /// Locked chapters are skipped, they would only return an error page.
+    pub fn request(&mut self, fiction: usize, references: &[ChapterReference]) {
+        let references = references
+            .iter()
+            .filter(|r| {
+                !r.locked && !self.chapters.contains_key(&r.path) && !self.pending.contains(&r.path)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if references.is_empty() {
+            return;
+        }
+        self.pending
+            .extend(references.iter().map(|r| r.path.clone()));
+        let client = Arc::clone(&self.client);
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            for reference in references {
+                let chapter = Chapter::from_reference(&reference, &client);
+                if tx.send((fiction, chapter, reference.path)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Stores whatever finished since the last call and returns the new chapters with
+    /// their fiction id. Failed fetches are dropped, the chapter is simply fetched again
+    /// when it is opened.
+    pub fn poll(&mut self) -> Vec<(usize, Chapter)> {
+        let mut received = Vec::new();
+        while let Ok((fiction, chapter, path)) = self.rx.try_recv() {
+            self.pending.remove(&path);
+            if let Ok(chapter) = chapter {
+                received.push((fiction, chapter.clone()));
+                self.chapters.insert(path, chapter);
+            }
+        }
+        received
+    }
+
+    /// drops held chapters that are not in `paths`
+    pub fn retain(&mut self, paths: &[String]) {
+        self.chapters.retain(|path, _| paths.contains(path));
+    }
+}
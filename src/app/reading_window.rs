@@ -1,70 +1,410 @@
+use std::ops::Range;
+
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Rect},
-    style::Color,
-    text::Line,
+    style::Modifier,
+    text::{Line, Span},
     widgets::{StatefulWidget, Widget},
 };
+use regex::Regex;
 
 use tui_big_text::BigText;
 
+use super::config::Typography;
+use super::text;
+use super::theme::Theme;
+
+/// narrowest a column gets before paged mode falls back to a single column
+const MIN_COLUMN_WIDTH: u16 = 30;
+/// blank columns between the two pages of a spread
+const COLUMN_GAP: u16 = 6;
+
 pub struct ReadingWindow {
     margin: (u16, u16),
+    theme: Theme,
 }
 
 #[derive(Default)]
 pub struct ReadingWindowState {
     pub text: Vec<String>,
     pub is_reading: bool,
-    pub line: u16,
+    /// first visible line of the wrapped layout
+    pub line: usize,
+    /// visible height of the last render, what a page scroll moves by
+    pub height: usize,
+    /// pages side by side in the last render, 2 for a paged spread on a wide pane
+    columns: usize,
+    typography: Typography,
+    lines: Vec<String>,
+    /// lines `typography.justify` stretches to the full width, all but each paragraph's last
+    justified: Vec<bool>,
+    /// wrapped line each paragraph of `text` starts at
+    paragraph_starts: Vec<usize>,
+    /// for each of `lines`, where its text is in its paragraph, see `text::wrap_sources`
+    sources: Vec<Vec<(Range<usize>, Range<usize>)>>,
+    wrap_width: u16,
+    /// paragraph to put at the top once the text is wrapped
+    anchor: Option<usize>,
+    search: Option<TextSearch>,
+}
+
+/// A search over the paragraphs, so a match can run across a line break. Each match is
+/// cut into pieces on the wrapped lines to highlight.
+struct TextSearch {
+    /// what was typed, `regex` may be an escaped version of it
+    query: String,
+    regex: Regex,
+    /// wrapped line each match starts on
+    matches: Vec<usize>,
+    /// (line, byte range in the line, index into `matches`), in order
+    pieces: Vec<(usize, Range<usize>, usize)>,
+    /// index into `matches` of the match last jumped to
+    current: Option<usize>,
 }
 
 impl ReadingWindowState {
-    pub fn wrap_lines(
-        &self,
-        width: u16,
-        height: u16,
-        text: &Vec<String>,
-        margin: (u16, u16),
-    ) -> Vec<String> {
-        let mut wrapped_lines = vec![String::new(); margin.1 as usize];
-        let mut text_line = self.line;
-        let mut line_words = Vec::new();
-        while wrapped_lines.len() < height as usize && text_line < text.len() as u16 {
-            let mut cur_line = String::from_utf8(vec![b' '; margin.0 as usize]).unwrap();
-            if line_words.is_empty() {
-                line_words = text[text_line as usize].split(' ').rev().collect();
+    pub fn new(text: Vec<String>, line: usize, is_reading: bool) -> ReadingWindowState {
+        Self {
+            text,
+            line,
+            is_reading,
+            ..Self::default()
+        }
+    }
+
+    /// Replaces the chapter text. An active search carries over to the new text.
+    pub fn set_text(&mut self, text: Vec<String>) {
+        self.text = text;
+        self.line = 0;
+        self.lines.clear();
+        self.justified.clear();
+        self.paragraph_starts.clear();
+        self.sources.clear();
+        self.wrap_width = 0;
+        self.find_matches();
+    }
+
+    /// Starts searching for `regex`, typed as `query`, and jumps to the first match from the
+    /// top of the window, or clears the search with `None`.
+    pub fn set_search(&mut self, search: Option<(String, Regex)>) {
+        self.search = search.map(|(query, regex)| TextSearch {
+            query,
+            regex,
+            matches: Vec::new(),
+            pieces: Vec::new(),
+            current: None,
+        });
+        self.find_matches();
+        if let Some(search) = &mut self.search {
+            let first = search.matches.partition_point(|line| *line < self.line);
+            if first < search.matches.len() {
+                search.current = Some(first);
+                self.show_current_match();
             }
-            while !line_words.is_empty()
-                && line_words.last().unwrap().len() + cur_line.len()
-                    < (width as usize - margin.0 as usize * 2)
+        }
+    }
+
+    /// the query of the current search, as it was typed
+    pub fn search_query(&self) -> Option<&str> {
+        self.search.as_ref().map(|s| s.query.as_str())
+    }
+
+    /// (1-based current match, total matches) for display
+    pub fn match_position(&self) -> Option<(usize, usize)> {
+        let search = self.search.as_ref()?;
+        Some((search.current.map_or(0, |c| c + 1), search.matches.len()))
+    }
+
+    fn find_matches(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        search.matches.clear();
+        search.pieces.clear();
+        search.current = None;
+        // not wrapped yet
+        if self.paragraph_starts.len() != self.text.len() {
+            return;
+        }
+        for (p, paragraph) in self.text.iter().enumerate() {
+            let lines = self.paragraph_starts[p]
+                ..self
+                    .paragraph_starts
+                    .get(p + 1)
+                    .copied()
+                    .unwrap_or(self.lines.len());
+            for m in search
+                .regex
+                .find_iter(paragraph)
+                .filter(|m| !m.range().is_empty())
             {
-                if !cur_line.is_empty() {
-                    cur_line.push(' ');
+                let index = search.matches.len();
+                for line in lines.clone() {
+                    for (in_line, source) in &self.sources[line] {
+                        if source.end <= m.start() || m.end() <= source.start {
+                            continue;
+                        }
+                        // a gap is highlighted whole, a word just where it matched
+                        let range = if in_line.len() == source.len() {
+                            in_line.start + m.start().max(source.start) - source.start
+                                ..in_line.start + m.end().min(source.end) - source.start
+                        } else {
+                            in_line.clone()
+                        };
+                        if search.matches.len() == index {
+                            search.matches.push(line);
+                        }
+                        search.pieces.push((line, range, index));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves to the next (or previous) match, wrapping around the chapter.
+    /// Returns false if there is nothing to move to.
+    pub fn step_match(&mut self, forward: bool) -> bool {
+        let Some(search) = &mut self.search else {
+            return false;
+        };
+        let len = search.matches.len();
+        if len == 0 {
+            return false;
+        }
+        search.current = Some(match (search.current, forward) {
+            (Some(c), true) => (c + 1) % len,
+            (Some(c), false) => (c + len - 1) % len,
+            (None, true) => search
+                .matches
+                .partition_point(|line| *line < self.line)
+                .min(len - 1),
+            (None, false) => search
+                .matches
+                .partition_point(|line| *line < self.line)
+                .saturating_sub(1),
+        });
+        self.show_current_match();
+        true
+    }
+
+    /// scrolls so the current match sits a third of the way down, if it is off screen
+    fn show_current_match(&mut self) {
+        let Some(line) = self
+            .search
+            .as_ref()
+            .and_then(|s| s.matches.get(s.current?))
+            .copied()
+        else {
+            return;
+        };
+        if line < self.line || line >= self.line + self.visible() {
+            self.line = if self.typography.paged {
+                self.page_start(line)
+            } else {
+                line.saturating_sub(self.height / 3)
+            }
+            .min(self.max_line());
+        }
+    }
+
+    /// the wrapped line split into plain and highlighted spans
+    fn highlight_line<'a>(&self, i: usize, line: &'a str, theme: &Theme) -> Line<'a> {
+        let Some(search) = &self.search else {
+            return Line::styled(line, theme.text);
+        };
+        let start = search.pieces.partition_point(|(l, _, _)| *l < i);
+        let mut spans = Vec::new();
+        let mut end = 0;
+        for (_, range, index) in search.pieces[start..]
+            .iter()
+            .take_while(|(l, _, _)| *l == i)
+        {
+            spans.push(Span::styled(&line[end..range.start], theme.text));
+            let style = if search.current == Some(*index) {
+                theme.highlight.add_modifier(Modifier::REVERSED)
+            } else {
+                theme.highlight
+            };
+            spans.push(Span::styled(&line[range.clone()], style));
+            end = range.end;
+        }
+        spans.push(Span::styled(&line[end..], theme.text));
+        Line::from(spans)
+    }
+
+    pub fn typography(&self) -> Typography {
+        self.typography
+    }
+
+    /// Changes the layout, the text is rewrapped on the next render at the same paragraph.
+    pub fn set_typography(&mut self, typography: Typography) {
+        if typography != self.typography {
+            self.typography = typography;
+            self.wrap_width = 0;
+        }
+    }
+
+    /// Wraps every paragraph of `text` to `width` columns, spaced out as `typography` says,
+    /// and records the line each paragraph starts at.
+    fn wrap_lines(&mut self, width: u16) {
+        let typography = self.typography;
+        self.lines.clear();
+        self.justified.clear();
+        self.paragraph_starts.clear();
+        self.sources.clear();
+        for paragraph in &self.text {
+            self.paragraph_starts.push(self.lines.len());
+            let wrapped = text::wrap(
+                paragraph,
+                width as usize,
+                typography.indent as usize,
+                typography.hyphenate,
+            );
+            let sources = text::wrap_sources(paragraph, &wrapped);
+            let last = wrapped.len() - 1;
+            for (i, (line, source)) in wrapped.into_iter().zip(sources).enumerate() {
+                if i > 0 {
+                    for _ in 0..typography.line_spacing {
+                        self.lines.push(String::new());
+                        self.justified.push(false);
+                        self.sources.push(Vec::new());
+                    }
                 }
-                cur_line.push_str(line_words.pop().unwrap());
+                self.lines.push(line);
+                self.justified.push(typography.justify && i < last);
+                self.sources.push(source);
             }
-            wrapped_lines.push(cur_line);
-            if line_words.is_empty() {
-                text_line += 1;
-                wrapped_lines.push(String::new());
+            for _ in 0..typography.paragraph_spacing {
+                self.lines.push(String::new());
+                self.justified.push(false);
+                self.sources.push(Vec::new());
             }
         }
-        wrapped_lines
     }
 
-    pub fn new(text: Vec<String>, line: u16, is_reading: bool) -> ReadingWindowState {
-        Self {
-            text,
-            line,
-            is_reading,
+    /// paragraph shown at the top of the window
+    pub fn top_paragraph(&self) -> usize {
+        self.paragraph_starts
+            .partition_point(|start| *start <= self.line)
+            .saturating_sub(1)
+    }
+
+    /// scrolls so paragraph `paragraph` of `text` is at the top of the window
+    pub fn show_paragraph(&mut self, paragraph: usize) {
+        if self.wrap_width == 0 {
+            self.anchor = Some(paragraph);
+        } else if let Some(line) = self.paragraph_starts.get(paragraph) {
+            self.line = (*line).min(self.max_line());
         }
     }
+
+    /// rewraps for a new width, keeping the top paragraph in view
+    fn rewrap(&mut self, width: u16) {
+        let paragraph = self.anchor.take().unwrap_or_else(|| self.top_paragraph());
+        self.wrap_lines(width);
+        self.wrap_width = width;
+        self.line = self.paragraph_starts.get(paragraph).copied().unwrap_or(0);
+        self.find_matches();
+    }
+
+    /// lines on screen at once, every column of a spread
+    fn visible(&self) -> usize {
+        self.height.max(1) * self.columns.max(1)
+    }
+
+    /// first line of the page `line` is on
+    fn page_start(&self, line: usize) -> usize {
+        line - line % self.height.max(1)
+    }
+
+    /// (1-based first page on screen, total pages) in paged mode
+    pub fn page_position(&self) -> Option<(usize, usize)> {
+        if !self.typography.paged || !self.is_reading || self.wrap_width == 0 {
+            return None;
+        }
+        let height = self.height.max(1);
+        Some((
+            self.line / height + 1,
+            self.lines.len().div_ceil(height).max(1),
+        ))
+    }
+
+    /// In paged mode the last spread starts on a page boundary, even if it is part empty.
+    fn max_line(&self) -> usize {
+        if self.typography.paged {
+            let height = self.height.max(1);
+            let pages = self.lines.len().div_ceil(height);
+            pages.saturating_sub(self.columns.max(1)) * height
+        } else {
+            self.lines.len().saturating_sub(self.height)
+        }
+    }
+
+    /// true once the last line of the chapter is on screen
+    pub fn at_bottom(&self) -> bool {
+        self.line >= self.max_line()
+    }
+
+    /// Scrolls by lines, or by pages in paged mode where the view stays on page boundaries.
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.line = (self.line + lines * self.unit()).min(self.max_line());
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.line = self.line.saturating_sub(lines * self.unit());
+    }
+
+    fn unit(&self) -> usize {
+        if self.typography.paged {
+            self.height.max(1)
+        } else {
+            1
+        }
+    }
+
+    /// a whole screen, in paged mode a full spread
+    pub fn page_down(&mut self) {
+        self.line = (self.line + self.visible()).min(self.max_line());
+    }
+
+    pub fn page_up(&mut self) {
+        self.line = self.line.saturating_sub(self.visible());
+    }
+
+    /// half a screen, in paged mode a single page
+    pub fn half_page_down(&mut self) {
+        if self.typography.paged {
+            self.scroll_down(1);
+        } else {
+            self.scroll_down((self.height / 2).max(1));
+        }
+    }
+
+    pub fn half_page_up(&mut self) {
+        if self.typography.paged {
+            self.scroll_up(1);
+        } else {
+            self.scroll_up((self.height / 2).max(1));
+        }
+    }
+
+    pub fn top(&mut self) {
+        self.line = 0;
+    }
+
+    pub fn bottom(&mut self) {
+        self.line = self.max_line();
+    }
+
+    pub fn jump_percent(&mut self, percent: usize) {
+        self.line = (self.lines.len() * percent.min(100) / 100).min(self.max_line());
+    }
 }
 
 impl ReadingWindow {
-    pub fn new(margin: (u16, u16)) -> ReadingWindow {
-        Self { margin }
+    pub fn new(margin: (u16, u16), theme: Theme) -> ReadingWindow {
+        Self { margin, theme }
     }
 }
 
@@ -72,32 +412,70 @@ impl StatefulWidget for ReadingWindow {
     type State = ReadingWindowState;
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         if state.is_reading {
-            state.line = state.line.clamp(0, state.text.len() as u16 - 2);
-            let lines = state.wrap_lines(area.width - 2, area.height - 2, &state.text, self.margin);
-            for (i, line) in lines.into_iter().enumerate() {
-                buf.set_line(
-                    area.x + 1,
-                    area.y + i as u16,
-                    &Line::styled(line, Color::White),
-                    area.width,
-                );
+            let available = area.width.saturating_sub(2 + self.margin.0 * 2);
+            let columns =
+                if state.typography.paged && available >= 2 * MIN_COLUMN_WIDTH + COLUMN_GAP {
+                    2
+                } else {
+                    1
+                };
+            let mut width = (available - COLUMN_GAP * (columns - 1)) / columns;
+            if state.typography.max_width > 0 {
+                width = width.min(state.typography.max_width);
+            }
+            let spread = width * columns + COLUMN_GAP * (columns - 1);
+            let text_area = Rect {
+                x: area.x + 1 + self.margin.0 + (available - spread) / 2,
+                y: area.y + self.margin.1,
+                width,
+                height: area.height.saturating_sub(self.margin.1 * 2),
+            };
+            if text_area.width == 0 {
+                return;
+            }
+            if state.wrap_width != text_area.width {
+                state.rewrap(text_area.width);
+            }
+            state.height = text_area.height as usize;
+            state.columns = columns as usize;
+            state.line = state.line.min(state.max_line());
+            if state.typography.paged {
+                state.line = state.page_start(state.line);
+            }
+            for column in 0..columns {
+                let first = state.line + column as usize * state.height;
+                let x = text_area.x + column * (width + COLUMN_GAP);
+                for (i, line) in state
+                    .lines
+                    .iter()
+                    .enumerate()
+                    .skip(first)
+                    .take(state.height)
+                {
+                    let mut line = state.highlight_line(i, line, &self.theme);
+                    if state.justified[i] {
+                        line = text::justify(line, width as usize);
+                    }
+                    buf.set_line(x, text_area.y + (i - first) as u16, &line, width);
+                }
             }
         } else {
+            // two lines of 8 row tall letters, centered when there is room
+            let top = area.height.saturating_sub(16) / 2;
             BigText::builder()
-                .style(Color::White)
+                .style(self.theme.text)
                 .alignment(Alignment::Center)
                 .lines(vec![
-                    Line::styled("Royal", Color::White),
-                    Line::styled("Rust", Color::White),
+                    Line::styled("Royal", self.theme.text),
+                    Line::styled("Rust", self.theme.text),
                 ])
                 .build()
                 .unwrap()
                 .render(
                     Rect {
-                        x: area.x,
-                        y: (area.y + area.height) / 2 - 7,
-                        height: area.height,
-                        width: area.width,
+                        y: area.y + top,
+                        height: area.height - top,
+                        ..area
                     },
                     buf,
                 );
@@ -0,0 +1,324 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph, StatefulWidget, Widget, Wrap},
+};
+
+use royal_api::{Fiction, FictionLink, RoyalClient, RoyalError, SearchResult};
+
+use super::input::{TextInput, TextInputWidget};
+use super::text;
+use super::theme::Theme;
+
+/// how long typing has to pause before a search is sent
+const DEBOUNCE: Duration = Duration::from_millis(400);
+const MIN_QUERY_LEN: usize = 3;
+
+/// Runs title searches and loads previews on background threads, so typing never waits
+/// on the network.
+pub struct Searcher {
+    client: Arc<RoyalClient>,
+    /// the last query sent, results for any other one are stale
+    latest: Option<String>,
+    tx: Sender<(String, Result<Vec<SearchResult>, RoyalError>)>,
+    rx: Receiver<(String, Result<Vec<SearchResult>, RoyalError>)>,
+    /// the last fiction asked for, by id
+    latest_preview: Option<usize>,
+    preview_tx: Sender<(usize, Result<Fiction, RoyalError>)>,
+    preview_rx: Receiver<(usize, Result<Fiction, RoyalError>)>,
+}
+
+impl Searcher {
+    pub fn new(client: Arc<RoyalClient>) -> Searcher {
+        let (tx, rx) = channel();
+        let (preview_tx, preview_rx) = channel();
+        Self {
+            client,
+            latest: None,
+            tx,
+            rx,
+            latest_preview: None,
+            preview_tx,
+            preview_rx,
+        }
+    }
+
+    pub fn request(&mut self, query: String) {
+        self.latest = Some(query.clone());
+        let client = Arc::clone(&self.client);
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let results = client.search(&query);
+            let _ = tx.send((query, results));
+        });
+    }
+
+    /// the results of the last query sent, if they arrived; older ones are dropped
+    pub fn poll(&mut self) -> Option<(String, Result<Vec<SearchResult>, RoyalError>)> {
+        let mut current = None;
+        while let Ok((query, results)) = self.rx.try_recv() {
+            if self.latest.as_ref() == Some(&query) {
+                self.latest = None;
+                current = Some((query, results));
+            }
+        }
+        current
+    }
+
+    pub fn request_preview(&mut self, id: usize) {
+        self.latest_preview = Some(id);
+        let client = Arc::clone(&self.client);
+        let tx = self.preview_tx.clone();
+        thread::spawn(move || {
+            let fiction = client.get_fiction(id);
+            let _ = tx.send((id, fiction));
+        });
+    }
+
+    /// the fiction last asked for, if it arrived; older ones are dropped
+    pub fn poll_preview(&mut self) -> Option<(usize, Result<Fiction, RoyalError>)> {
+        let mut current = None;
+        while let Ok((id, fiction)) = self.preview_rx.try_recv() {
+            if self.latest_preview == Some(id) {
+                self.latest_preview = None;
+                current = Some((id, fiction));
+            }
+        }
+        current
+    }
+}
+
+pub struct SearchWidget {
+    theme: Theme,
+}
+
+pub struct SearchState {
+    pub input: TextInput,
+    pub results: Vec<SearchResult>,
+    pub selected: usize,
+    top: usize,
+    /// the fiction behind the selected result, shown before it is added
+    pub preview: Option<Fiction>,
+    /// id of the result whose preview is on its way
+    pub loading: Option<usize>,
+    /// query the current results belong to
+    searched: String,
+    last_edit: Instant,
+    pub status: Option<String>,
+}
+
+impl SearchState {
+    pub fn new() -> SearchState {
+        Self {
+            input: TextInput::default(),
+            results: Vec::new(),
+            selected: 0,
+            top: 0,
+            preview: None,
+            loading: None,
+            searched: String::new(),
+            last_edit: Instant::now(),
+            status: None,
+        }
+    }
+
+    /// typing also drops a preview still on its way
+    pub fn edited(&mut self) {
+        self.last_edit = Instant::now();
+        self.loading = None;
+    }
+
+    pub fn link(&self) -> Option<FictionLink> {
+        self.input.text.parse::<FictionLink>().ok()
+    }
+
+    /// the query to send now, once typing has paused on something that is not a link
+    pub fn pending_query(&self) -> Option<String> {
+        let query = self.input.text.trim();
+        if query == self.searched
+            || query.chars().count() < MIN_QUERY_LEN
+            || self.last_edit.elapsed() < DEBOUNCE
+            || self.link().is_some()
+        {
+            return None;
+        }
+        Some(query.to_string())
+    }
+
+    /// `query` was sent, it is not sent again while the results are on their way
+    pub fn searching(&mut self, query: String) {
+        self.searched = query;
+        self.status = Some(String::from("searching…"));
+    }
+
+    /// Shows the results of `query`, unless the input has been searched for again since.
+    pub fn set_results(&mut self, query: &str, results: Result<Vec<SearchResult>, RoyalError>) {
+        if query != self.searched {
+            return;
+        }
+        let (status, results) = match results {
+            Ok(results) => {
+                let status = match results.len() {
+                    0 => String::from("no results"),
+                    1 => String::from("1 result"),
+                    n => format!("{} results", n),
+                };
+                (status, results)
+            }
+            Err(e) => (format!("search failed: {}", e), Vec::new()),
+        };
+        self.status = Some(status);
+        self.results = results;
+        self.selected = 0;
+        self.top = 0;
+    }
+
+    /// Shows the preview of result `id`, unless it was given up on since.
+    pub fn set_preview(&mut self, id: usize, fiction: Result<Fiction, RoyalError>) {
+        if self.loading != Some(id) {
+            return;
+        }
+        self.loading = None;
+        match fiction {
+            Ok(fiction) => self.preview = Some(fiction),
+            Err(e) => self.input.error = Some(format!("failed to load fiction: {}", e)),
+        }
+    }
+
+    pub fn selected_result(&self) -> Option<&SearchResult> {
+        self.results.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.results.len().saturating_sub(1));
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+impl SearchWidget {
+    pub fn new(theme: Theme) -> SearchWidget {
+        Self { theme }
+    }
+}
+
+fn result_line(result: &SearchResult, width: usize) -> String {
+    let mut info = Vec::new();
+    if let Some(author) = &result.author {
+        info.push(author.clone());
+    }
+    if let Some(pages) = result.pages {
+        info.push(format!("{} pages", pages));
+    }
+    if let Some(rating) = result.rating {
+        info.push(format!("★ {:.2}", rating));
+    }
+    let info = info.join(" · ");
+    let info_len = text::width(&info);
+    let title = text::truncate(&result.title, width.saturating_sub(info_len + 2), "…");
+    let spacing = width.saturating_sub(text::width(&title) + info_len);
+    format!("{}{}{}", title, " ".repeat(spacing), info)
+}
+
+impl StatefulWidget for SearchWidget {
+    type State = SearchState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        Clear.render(area, buf);
+        Block::new()
+            .title("Add Fiction")
+            .style(self.theme.text)
+            .borders(Borders::ALL)
+            .render(area, buf);
+        let inner = Rect {
+            x: area.x + 2,
+            y: area.y + 1,
+            width: area.width.saturating_sub(4),
+            height: area.height.saturating_sub(2),
+        };
+        if inner.width == 0 || inner.height < 2 {
+            return;
+        }
+        TextInputWidget::new(self.theme).render(Rect { height: 2, ..inner }, buf, &mut state.input);
+        if state.input.error.is_none() {
+            let hint = match (&state.preview, state.loading, state.link()) {
+                (Some(_), _, _) => Some("enter: add, esc: back to results"),
+                (None, Some(_), _) => Some("loading fiction…"),
+                (None, None, Some(_)) => Some("enter: open link"),
+                _ => None,
+            };
+            if let Some(status) = hint.or(state.status.as_deref()) {
+                buf.set_line(
+                    inner.x,
+                    inner.y + 1,
+                    &Line::styled(status, self.theme.dim),
+                    inner.width,
+                );
+            }
+        }
+        let body = Rect {
+            y: inner.y + 3,
+            height: inner.height.saturating_sub(3),
+            ..inner
+        };
+        if let Some(fiction) = &state.preview {
+            let latest = fiction
+                .chapters
+                .last()
+                .map(|c| c.title.as_str())
+                .unwrap_or("none");
+            let mut lines = vec![
+                Line::styled(fiction.title.as_str(), self.theme.border),
+                Line::styled(format!("by {}", fiction.author), self.theme.dim),
+                Line::styled(
+                    format!("{} chapters, latest: {}", fiction.chapters.len(), latest),
+                    self.theme.dim,
+                ),
+                Line::raw(""),
+            ];
+            lines.extend(
+                fiction
+                    .description
+                    .lines()
+                    .map(|l| Line::styled(l.trim(), self.theme.text)),
+            );
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: true })
+                .render(body, buf);
+            return;
+        }
+        let height = body.height as usize;
+        if height == 0 {
+            return;
+        }
+        state.top = state
+            .top
+            .clamp((state.selected + 1).saturating_sub(height), state.selected);
+        for (i, result) in state
+            .results
+            .iter()
+            .enumerate()
+            .skip(state.top)
+            .take(height)
+        {
+            let style = if i == state.selected {
+                self.theme.selection
+            } else {
+                self.theme.text
+            };
+            buf.set_line(
+                body.x,
+                body.y + (i - state.top) as u16,
+                &Line::styled(result_line(result, body.width as usize), style),
+                body.width,
+            );
+        }
+    }
+}
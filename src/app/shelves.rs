@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::config::config_dir;
+
+const DEFAULT_SHELVES: &[&str] = &["Reading", "Caught up", "On hold", "Dropped", "Finished"];
+
+/// Where one fiction is kept, and what the reader tagged it with.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+struct Entry {
+    shelf: String,
+    tags: Vec<String>,
+}
+
+/// Shelves and tags of the library, saved next to `fictions.txt` as `shelves.json`.
+/// The shelf names can be edited there; a fiction on no known shelf is on the first one.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Shelves {
+    /// in the order the sidebar switches through them
+    pub names: Vec<String>,
+    /// by fiction id
+    fictions: HashMap<usize, Entry>,
+}
+
+impl Default for Shelves {
+    fn default() -> Self {
+        Self {
+            names: DEFAULT_SHELVES.iter().map(|s| s.to_string()).collect(),
+            fictions: HashMap::new(),
+        }
+    }
+}
+
+impl Shelves {
+    pub fn path() -> PathBuf {
+        config_dir().join("shelves.json")
+    }
+
+    /// A missing file is the default shelves, a broken one is an error.
+    pub fn load() -> Result<Shelves, String> {
+        let mut shelves = match read_to_string(Self::path()) {
+            Ok(text) => serde_json::from_str::<Shelves>(&text)
+                .map_err(|e| format!("invalid shelves.json: {}", e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Shelves::default(),
+            Err(e) => return Err(format!("failed to read shelves.json: {}", e)),
+        };
+        if shelves.names.is_empty() {
+            shelves.names = Shelves::default().names;
+        }
+        Ok(shelves)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// index into `names` of the shelf `fiction` is on
+    pub fn shelf(&self, fiction: usize) -> usize {
+        self.fictions
+            .get(&fiction)
+            .and_then(|entry| self.names.iter().position(|n| *n == entry.shelf))
+            .unwrap_or(0)
+    }
+
+    pub fn set_shelf(&mut self, fiction: usize, shelf: usize) {
+        if let Some(name) = self.names.get(shelf) {
+            self.fictions.entry(fiction).or_default().shelf = name.clone();
+        }
+    }
+
+    /// moves `fiction` to the next shelf, or the previous one, wrapping around
+    pub fn step_shelf(&mut self, fiction: usize, forward: bool) {
+        let len = self.names.len();
+        let shelf = if forward {
+            (self.shelf(fiction) + 1) % len
+        } else {
+            (self.shelf(fiction) + len - 1) % len
+        };
+        self.set_shelf(fiction, shelf);
+    }
+
+    pub fn tags(&self, fiction: usize) -> &[String] {
+        self.fictions
+            .get(&fiction)
+            .map_or(&[], |e| e.tags.as_slice())
+    }
+
+    /// Sets the tags of `fiction` from a comma separated list, dropping empty and repeated ones.
+    pub fn set_tags(&mut self, fiction: usize, tags: &str) {
+        let mut parsed: Vec<String> = Vec::new();
+        for tag in tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if !parsed.iter().any(|t| t == tag) {
+                parsed.push(tag.to_string());
+            }
+        }
+        let shelf = self.shelf(fiction);
+        self.set_shelf(fiction, shelf);
+        self.fictions.get_mut(&fiction).unwrap().tags = parsed;
+    }
+
+    /// Adds a shelf after the others, returning its index.
+    pub fn add(&mut self, name: &str) -> Result<usize, String> {
+        let name = self.check_name(name)?;
+        self.names.push(name);
+        Ok(self.names.len() - 1)
+    }
+
+    /// Renames shelf `shelf`, keeping the fictions on it.
+    pub fn rename(&mut self, shelf: usize, name: &str) -> Result<(), String> {
+        let name = self.check_name(name)?;
+        let old = std::mem::replace(&mut self.names[shelf], name.clone());
+        for entry in self.fictions.values_mut() {
+            if entry.shelf == old {
+                entry.shelf = name.clone();
+            }
+        }
+        Ok(())
+    }
+
+    fn check_name(&self, name: &str) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(String::from("type a shelf name"));
+        }
+        if self.names.iter().any(|n| n == name) {
+            return Err(format!("there is already a shelf \"{}\"", name));
+        }
+        Ok(name.to_string())
+    }
+
+    /// forgets the shelf and tags of a fiction deleted from the library
+    pub fn forget(&mut self, fiction: usize) {
+        self.fictions.remove(&fiction);
+    }
+}
@@ -0,0 +1,342 @@
+use std::ops::Range;
+
+use ratatui::text::{Line, Span};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// shortest word hyphenation breaks, and the fewest graphemes left on either side
+const HYPHENATE_MIN_WORD: usize = 6;
+const HYPHENATE_MIN_PART: usize = 3;
+
+/// columns `text` takes up in the terminal
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+
+/// Cuts `text` to at most `max` columns, ending it with `ellipsis` if anything was cut.
+/// Never splits a grapheme, so a wide character that does not fit is dropped whole.
+pub fn truncate(text: &str, max: usize, ellipsis: &str) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    // no room for the ellipsis either, cut to whatever fits
+    let ellipsis = if width(ellipsis) > max { "" } else { ellipsis };
+    let max = max - width(ellipsis);
+    let mut cut = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        used += width(grapheme);
+        if used > max {
+            break;
+        }
+        cut.push_str(grapheme);
+    }
+    cut.push_str(ellipsis);
+    cut
+}
+
+/// whitespace a line may break at, non-breaking spaces excluded
+fn is_break(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+/// Splits `word` so the first part, including a trailing hyphen when `hyphen` is set,
+/// fits in `room` columns. The first part is empty if not even one grapheme fits.
+fn split_word(word: &str, room: usize, hyphen: bool) -> (&str, &str) {
+    let room = room.saturating_sub(hyphen as usize);
+    let mut used = 0;
+    let mut end = 0;
+    for (i, grapheme) in word.grapheme_indices(true) {
+        used += width(grapheme);
+        if used > room {
+            break;
+        }
+        end = i + grapheme.len();
+    }
+    word.split_at(end)
+}
+
+/// Where to hyphenate `word` to fit `room` columns, if it is long enough to be worth it.
+/// There is no dictionary, the break just keeps a few letters on both sides.
+fn hyphenate(word: &str, room: usize) -> Option<(&str, &str)> {
+    let graphemes = word.graphemes(true).count();
+    if graphemes < HYPHENATE_MIN_WORD || !word.chars().all(char::is_alphabetic) {
+        return None;
+    }
+    let (head, tail) = split_word(word, room, true);
+    let head_len = head.graphemes(true).count();
+    (head_len >= HYPHENATE_MIN_PART && graphemes - head_len >= HYPHENATE_MIN_PART)
+        .then_some((head, tail))
+}
+
+/// Wraps `paragraph` to lines at most `max` columns wide, measured by display width,
+/// with the first line indented by `indent` spaces.
+/// Words wider than a line are broken wherever they have to be; with `hyphenated`
+/// long words at the end of a line are also split with a hyphen to fill it. A hyphen
+/// only goes between two letters.
+pub fn wrap(paragraph: &str, max: usize, indent: usize, hyphenated: bool) -> Vec<String> {
+    let max = max.max(1);
+    let mut lines = Vec::new();
+    // where the text of the current line starts, only the first line is indented
+    let mut start = indent.min(max - 1);
+    let mut line = " ".repeat(start);
+    let mut used = start;
+    let mut words = paragraph
+        .split(is_break)
+        .filter(|w| !w.is_empty())
+        .rev()
+        .collect::<Vec<_>>();
+    while let Some(word) = words.pop() {
+        let gap = (used > start) as usize;
+        let word_width = width(word);
+        if used + gap + word_width <= max {
+            if gap == 1 {
+                line.push(' ');
+            }
+            line.push_str(word);
+            used += gap + word_width;
+            continue;
+        }
+        let room = max.saturating_sub(used + gap);
+        let split = if word_width > max {
+            // a word no line can hold is broken, hyphenated if that was asked for
+            let (head, tail) = split_word(word, room, hyphenated);
+            (!head.is_empty()).then_some((head, tail))
+        } else if hyphenated {
+            hyphenate(word, room)
+        } else {
+            None
+        };
+        match split {
+            Some((head, tail)) => {
+                if gap == 1 {
+                    line.push(' ');
+                }
+                line.push_str(head);
+                // a break inside a number or between punctuation is not a hyphenation
+                let letters = head.chars().next_back().is_some_and(char::is_alphabetic)
+                    && tail.chars().next().is_some_and(char::is_alphabetic);
+                if hyphenated && letters {
+                    line.push('-');
+                }
+                words.push(tail);
+            }
+            None if used == start => {
+                // not even one grapheme fits, it goes over the edge rather than looping
+                let (head, tail) = word
+                    .grapheme_indices(true)
+                    .nth(1)
+                    .map_or((word, ""), |(i, _)| word.split_at(i));
+                line.push_str(head);
+                if !tail.is_empty() {
+                    words.push(tail);
+                }
+            }
+            None => words.push(word),
+        }
+        lines.push(std::mem::take(&mut line));
+        start = 0;
+        used = 0;
+    }
+    if used > start || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// For each line `wrap` made of `paragraph`, the parts of the line that came from it as
+/// (byte range in the line, byte range in `paragraph`) pairs, in order. A gap between two
+/// words stands for the whitespace it replaced; the indent and inserted hyphens map to nothing.
+pub fn wrap_sources(paragraph: &str, lines: &[String]) -> Vec<Vec<(Range<usize>, Range<usize>)>> {
+    let mut cursor = 0;
+    let skip_breaks = |cursor: &mut usize| {
+        let rest = &paragraph[*cursor..];
+        *cursor += rest.len() - rest.trim_start_matches(is_break).len();
+    };
+    let mut sources = Vec::with_capacity(lines.len());
+    for line in lines {
+        let mut parts = Vec::new();
+        let mut pos = line.len() - line.trim_start_matches(' ').len();
+        for (i, word) in line[pos..].split(' ').enumerate() {
+            let gap_start = cursor;
+            skip_breaks(&mut cursor);
+            if i > 0 {
+                parts.push((pos - 1..pos, gap_start..cursor));
+            }
+            let rest = &paragraph[cursor..];
+            let len = if rest.starts_with(word) {
+                word.len()
+            } else {
+                match word.strip_suffix('-') {
+                    Some(head) if rest.starts_with(head) => head.len(),
+                    // not a line of this paragraph, nothing more lines up
+                    _ => break,
+                }
+            };
+            if len > 0 {
+                parts.push((pos..pos + len, cursor..cursor + len));
+            }
+            cursor += len;
+            pos += word.len() + 1;
+        }
+        sources.push(parts);
+    }
+    sources
+}
+
+/// Stretches the spaces between the words of `line` until it is `max` columns wide.
+/// Leading spaces are an indent and stay as they are.
+pub fn justify(line: Line<'_>, max: usize) -> Line<'_> {
+    let mut leading = true;
+    let gaps = line
+        .spans
+        .iter()
+        .flat_map(|span| span.content.chars())
+        .filter(|c| {
+            leading &= *c == ' ';
+            *c == ' ' && !leading
+        })
+        .count();
+    let used = line.width();
+    if gaps == 0 || used >= max {
+        return line;
+    }
+    let (each, rest) = ((max - used) / gaps, (max - used) % gaps);
+    let style = line.style;
+    let mut leading = true;
+    let mut gap = 0;
+    let spans = line
+        .spans
+        .into_iter()
+        .map(|span| {
+            let mut content = String::with_capacity(span.content.len());
+            for c in span.content.chars() {
+                content.push(c);
+                leading &= c == ' ';
+                if c == ' ' && !leading {
+                    let extra = each + (gap < rest) as usize;
+                    content.push_str(&" ".repeat(extra));
+                    gap += 1;
+                }
+            }
+            Span::styled(content, span.style)
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans).style(style)
+}
+
+/// Matches `pattern` against `text` as a subsequence, ignoring case and the spaces in
+/// `pattern`. Returns the char indices of `text` that matched, or `None` if it does not.
+/// The match is the shortest window that ends where the first match ends, so `ol` in
+/// "Mother of Learning" marks the `o` of "of" rather than the one in "Mother".
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<Vec<usize>> {
+    let pattern = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+    let chars = text.chars().collect::<Vec<_>>();
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+    if pattern.is_empty() {
+        return Some(Vec::new());
+    }
+    let mut next = 0;
+    let end = chars.iter().position(|c| {
+        if same(*c, pattern[next]) {
+            next += 1;
+        }
+        next == pattern.len()
+    })?;
+    let mut positions = Vec::with_capacity(pattern.len());
+    for i in (0..=end).rev() {
+        if positions.len() == pattern.len() {
+            break;
+        }
+        if same(chars[i], pattern[pattern.len() - 1 - positions.len()]) {
+            positions.push(i);
+        }
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_breaks_words_longer_than_a_line() {
+        assert_eq!(wrap("abcdefghij", 4, 0, false), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("ab abcdefgh", 5, 0, false), ["ab ab", "cdefg", "h"]);
+    }
+
+    #[test]
+    fn wrap_survives_zero_width_and_large_indents() {
+        assert_eq!(wrap("abc", 0, 0, false), ["a", "b", "c"]);
+        assert_eq!(wrap("ab", 2, 5, false), [" a", "b"]);
+        assert_eq!(wrap("", 10, 2, false), ["  "]);
+    }
+
+    #[test]
+    fn wrap_measures_wide_chars_by_columns() {
+        assert_eq!(
+            wrap("日本語 テキスト", 6, 0, false),
+            ["日本語", "テキス", "ト"]
+        );
+        // a character wider than the line goes over the edge rather than looping
+        assert_eq!(wrap("日本", 1, 0, false), ["日", "本"]);
+    }
+
+    #[test]
+    fn wrap_hyphenates_only_between_letters() {
+        assert_eq!(
+            wrap("a wonderful day", 7, 0, true),
+            ["a wond-", "erful", "day"]
+        );
+        assert_eq!(wrap("hi there", 5, 0, true), ["hi", "there"]);
+        assert_eq!(wrap("abcdefghij", 4, 0, true), ["abc-", "def-", "ghij"]);
+        assert_eq!(wrap("1234567890", 4, 0, true), ["123", "456", "7890"]);
+        assert_eq!(wrap("abc...def", 4, 0, true), ["abc", "...", "def"]);
+    }
+
+    #[test]
+    fn truncate_keeps_graphemes_whole() {
+        assert_eq!(truncate("short", 10, "…"), "short");
+        assert_eq!(truncate("日本語", 5, "…"), "日本…");
+        assert_eq!(truncate("abc", 0, "…"), "");
+    }
+
+    #[test]
+    fn fuzzy_match_prefers_the_shortest_window() {
+        assert_eq!(fuzzy_match("ol", "Mother of Learning"), Some(vec![7, 10]));
+        assert_eq!(fuzzy_match("m L", "Mother of Learning"), Some(vec![0, 10]));
+        assert_eq!(fuzzy_match("", "anything"), Some(Vec::new()));
+        assert_eq!(fuzzy_match("xyz", "Mother of Learning"), None);
+        assert_eq!(fuzzy_match("ab", "ba"), None);
+    }
+
+    #[test]
+    fn wrap_sources_maps_lines_back_to_the_paragraph() {
+        let paragraph = "one  two\tthree";
+        let lines = wrap(paragraph, 9, 2, false);
+        assert_eq!(lines, ["  one two", "three"]);
+        let sources = wrap_sources(paragraph, &lines);
+        assert_eq!(
+            sources,
+            [
+                vec![(2..5, 0..3), (5..6, 3..5), (6..9, 5..8)],
+                vec![(0..5, 9..14)],
+            ]
+        );
+    }
+
+    #[test]
+    fn wrap_sources_skips_inserted_hyphens() {
+        let paragraph = "a wonderful day";
+        let lines = wrap(paragraph, 7, 0, true);
+        assert_eq!(lines, ["a wond-", "erful", "day"]);
+        let sources = wrap_sources(paragraph, &lines);
+        assert_eq!(sources[0], [(0..1, 0..1), (1..2, 1..2), (2..6, 2..6)]);
+        assert_eq!(sources[1], [(0..5, 6..11)]);
+        assert_eq!(sources[2], [(0..3, 12..15)]);
+    }
+}
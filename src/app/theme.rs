@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+/// Named styles used by every widget, so nothing hard-codes a color.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub border: Style,
+    pub selection: Style,
+    pub text: Style,
+    /// metadata like dates, counts and hints
+    pub dim: Style,
+    pub error: Style,
+    /// search matches in the chapter text
+    pub highlight: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub const BUILTIN: &'static [&'static str] = &["dark", "light", "solarized"];
+
+    pub fn dark() -> Theme {
+        Theme {
+            border: Style::default().fg(Color::Blue),
+            selection: Style::default().fg(Color::Black).bg(Color::Blue),
+            text: Style::default().fg(Color::White),
+            dim: Style::default().fg(Color::DarkGray),
+            error: Style::default().fg(Color::Red),
+            highlight: Style::default().fg(Color::Black).bg(Color::Yellow),
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            border: Style::default().fg(Color::Blue),
+            selection: Style::default().fg(Color::White).bg(Color::Blue),
+            text: Style::default().fg(Color::Black),
+            dim: Style::default().fg(Color::DarkGray),
+            error: Style::default().fg(Color::Red),
+            highlight: Style::default().fg(Color::Black).bg(Color::LightYellow),
+        }
+    }
+
+    pub fn solarized() -> Theme {
+        Theme {
+            border: Style::default().fg(Color::Rgb(0x26, 0x8b, 0xd2)),
+            selection: Style::default()
+                .fg(Color::Rgb(0xfd, 0xf6, 0xe3))
+                .bg(Color::Rgb(0x26, 0x8b, 0xd2)),
+            text: Style::default().fg(Color::Rgb(0x83, 0x94, 0x96)),
+            dim: Style::default().fg(Color::Rgb(0x58, 0x6e, 0x75)),
+            error: Style::default().fg(Color::Rgb(0xdc, 0x32, 0x2f)),
+            highlight: Style::default()
+                .fg(Color::Rgb(0x00, 0x2b, 0x36))
+                .bg(Color::Rgb(0xb5, 0x89, 0x00)),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "solarized" => Some(Self::solarized()),
+            _ => None,
+        }
+    }
+
+    /// Resolves `name` against the user's `[themes]` first, then the built-in themes.
+    /// A user theme starts from its `base` and overrides the styles it sets.
+    pub fn resolve(name: &str, themes: &HashMap<String, ThemeConfig>) -> Result<Theme, String> {
+        Self::resolve_depth(name, themes, 0)
+    }
+
+    fn resolve_depth(
+        name: &str,
+        themes: &HashMap<String, ThemeConfig>,
+        depth: usize,
+    ) -> Result<Theme, String> {
+        let config = match themes.get(name) {
+            Some(config) if depth <= themes.len() => config,
+            Some(_) => return Err(format!("theme \"{}\" has a cyclic base", name)),
+            None => {
+                return Self::builtin(name).ok_or_else(|| {
+                    format!(
+                        "unknown theme \"{}\", built-in themes are {}",
+                        name,
+                        Self::BUILTIN.join(", ")
+                    )
+                })
+            }
+        };
+        let base = config.base.as_deref().unwrap_or("dark");
+        // a user theme may shadow the built-in it is based on
+        let mut theme = if base == name {
+            Self::builtin(base).ok_or_else(|| format!("theme \"{}\" is its own base", name))?
+        } else {
+            Self::resolve_depth(base, themes, depth + 1)?
+        };
+        for (style, config) in [
+            (&mut theme.border, &config.border),
+            (&mut theme.selection, &config.selection),
+            (&mut theme.text, &config.text),
+            (&mut theme.dim, &config.dim),
+            (&mut theme.error, &config.error),
+            (&mut theme.highlight, &config.highlight),
+        ] {
+            if let Some(config) = config {
+                *style = config.apply(*style)?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// A user theme in `config.toml`, e.g.
+/// `[themes.mine]` with `base = "light"` and `border = { fg = "#268bd2", bold = true }`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub base: Option<String>,
+    pub border: Option<StyleConfig>,
+    pub selection: Option<StyleConfig>,
+    pub text: Option<StyleConfig>,
+    pub dim: Option<StyleConfig>,
+    pub error: Option<StyleConfig>,
+    pub highlight: Option<StyleConfig>,
+}
+
+/// Colors are names like `blue` / `light-blue`, `#rrggbb`, or 256 color indices.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct StyleConfig {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+}
+
+impl StyleConfig {
+    fn apply(&self, mut style: Style) -> Result<Style, String> {
+        let color = |s: &str| Color::from_str(s).map_err(|_| format!("invalid color \"{}\"", s));
+        if let Some(fg) = &self.fg {
+            style = style.fg(color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(color(bg)?);
+        }
+        for (set, modifier) in [
+            (self.bold, Modifier::BOLD),
+            (self.italic, Modifier::ITALIC),
+            (self.underlined, Modifier::UNDERLINED),
+        ] {
+            style = match set {
+                Some(true) => style.add_modifier(modifier),
+                Some(false) => style.remove_modifier(modifier),
+                None => style,
+            };
+        }
+        Ok(style)
+    }
+}
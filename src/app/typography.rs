@@ -0,0 +1,143 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    text::Line,
+    widgets::{Block, Borders, Clear, StatefulWidget, Widget},
+};
+
+use super::config::Typography;
+use super::theme::Theme;
+
+const LABELS: &[&str] = &[
+    "line width",
+    "justify",
+    "indent",
+    "line spacing",
+    "paragraph spacing",
+    "hyphenate",
+    "paged",
+];
+
+pub struct TypographyWidget {
+    theme: Theme,
+}
+
+/// The typography popup, editing a copy of the reading pane's settings.
+pub struct TypographyState {
+    pub typography: Typography,
+    /// the settings when the popup opened, Esc goes back to them
+    pub original: Typography,
+    selected: usize,
+}
+
+impl TypographyState {
+    pub fn new(typography: Typography) -> TypographyState {
+        Self {
+            typography,
+            original: typography,
+            selected: 0,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(LABELS.len() - 1);
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// steps the selected setting up or down, switches flip either way
+    pub fn adjust(&mut self, up: bool) {
+        let step = |value: u16, max: u16| {
+            if up {
+                (value + 1).min(max)
+            } else {
+                value.saturating_sub(1)
+            }
+        };
+        let t = &mut self.typography;
+        match self.selected {
+            // widths below 20 columns are not worth reading at, so stepping down ends at off
+            0 => {
+                t.max_width = match (t.max_width, up) {
+                    (0, true) => 60,
+                    (width, true) => (width + 5).min(500),
+                    (width, false) if width <= 20 => 0,
+                    (width, false) => width - 5,
+                }
+            }
+            1 => t.justify = !t.justify,
+            2 => t.indent = step(t.indent, 8),
+            3 => t.line_spacing = step(t.line_spacing, 3),
+            4 => t.paragraph_spacing = step(t.paragraph_spacing, 3),
+            5 => t.hyphenate = !t.hyphenate,
+            _ => t.paged = !t.paged,
+        }
+    }
+
+    fn value(&self, row: usize) -> String {
+        let t = &self.typography;
+        let switch = |on: bool| String::from(if on { "on" } else { "off" });
+        match row {
+            0 if t.max_width == 0 => String::from("fill pane"),
+            0 => format!("{} columns", t.max_width),
+            1 => switch(t.justify),
+            2 => t.indent.to_string(),
+            3 => t.line_spacing.to_string(),
+            4 => t.paragraph_spacing.to_string(),
+            5 => switch(t.hyphenate),
+            _ => switch(t.paged),
+        }
+    }
+}
+
+impl TypographyWidget {
+    pub fn new(theme: Theme) -> TypographyWidget {
+        Self { theme }
+    }
+}
+
+impl StatefulWidget for TypographyWidget {
+    type State = TypographyState;
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        Clear.render(area, buf);
+        Block::new()
+            .title(" Typography ")
+            .style(self.theme.text)
+            .borders(Borders::ALL)
+            .render(area, buf);
+        let inner = Rect {
+            x: area.x + 2,
+            y: area.y + 1,
+            width: area.width.saturating_sub(4),
+            height: area.height.saturating_sub(2),
+        };
+        if inner.width == 0 {
+            return;
+        }
+        for (row, label) in LABELS.iter().enumerate().take(inner.height as usize) {
+            let value = state.value(row);
+            let style = if row == state.selected {
+                self.theme.selection
+            } else {
+                self.theme.text
+            };
+            let spacing = (inner.width as usize).saturating_sub(label.len() + value.len());
+            buf.set_line(
+                inner.x,
+                inner.y + row as u16,
+                &Line::styled(format!("{}{}{}", label, " ".repeat(spacing), value), style),
+                inner.width,
+            );
+        }
+        if inner.height as usize > LABELS.len() + 1 {
+            buf.set_line(
+                inner.x,
+                inner.y + inner.height - 1,
+                &Line::styled("←→ change, enter saves, esc cancels", self.theme.dim),
+                inner.width,
+            );
+        }
+    }
+}
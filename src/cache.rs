@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read, read_dir, read_to_string, remove_dir_all, remove_file, write};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a path points at, used to pick how long a cached copy stays fresh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Fiction,
+    Chapter,
+    Image,
+    Other,
+}
+
+impl ResourceKind {
+    pub fn from_path(path: &str) -> ResourceKind {
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        let extension = path.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+        if ["jpg", "jpeg", "png", "gif", "webp"].contains(&extension.as_str()) {
+            ResourceKind::Image
+        } else if path.contains("/chapter/") {
+            ResourceKind::Chapter
+        } else if path.starts_with("/fiction/") {
+            ResourceKind::Fiction
+        } else {
+            ResourceKind::Other
+        }
+    }
+}
+
+/// How long a cached response is served without asking the server.
+/// Once stale it is revalidated with `If-None-Match` / `If-Modified-Since`.
+/// A kind with no max age is not cached at all.
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    pub fiction_max_age: Duration,
+    pub chapter_max_age: Duration,
+    pub image_max_age: Duration,
+    pub other_max_age: Duration,
+    /// bytes the cache may take on disk, the least recently fetched entries go first
+    /// past it; 0 for no limit
+    pub max_size: u64,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            fiction_max_age: Duration::from_secs(60 * 60),
+            chapter_max_age: Duration::from_secs(30 * 24 * 60 * 60),
+            image_max_age: Duration::from_secs(30 * 24 * 60 * 60),
+            other_max_age: Duration::ZERO,
+            max_size: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl CachePolicy {
+    pub fn max_age(&self, kind: ResourceKind) -> Duration {
+        match kind {
+            ResourceKind::Fiction => self.fiction_max_age,
+            ResourceKind::Chapter => self.chapter_max_age,
+            ResourceKind::Image => self.image_max_age,
+            ResourceKind::Other => self.other_max_age,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    pub path: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// unix time the body was last fetched or revalidated
+    pub fetched: u64,
+}
+
+#[derive(Debug)]
+pub struct CachedResponse {
+    pub entry: CacheEntry,
+    pub body: Vec<u8>,
+}
+
+/// On-disk response cache, one metadata file and one body file per path.
+/// Failing to read or write the cache is never fatal, it just means a refetch.
+#[derive(Debug)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    policy: CachePolicy,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// fnv-1a, so file names stay the same across builds
+fn key(path: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in path.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf) -> ResponseCache {
+        Self::with_policy(dir, CachePolicy::default())
+    }
+
+    pub fn with_policy(dir: PathBuf, policy: CachePolicy) -> ResponseCache {
+        Self { dir, policy }
+    }
+
+    pub fn default_dir() -> Option<PathBuf> {
+        Some(dirs::cache_dir()?.join("royal_rust").join("responses"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn policy(&self) -> &CachePolicy {
+        &self.policy
+    }
+
+    fn entry_path(&self, path: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key(path)))
+    }
+
+    fn body_path(&self, path: &str) -> PathBuf {
+        self.dir.join(format!("{}.body", key(path)))
+    }
+
+    pub fn lookup(&self, path: &str) -> Option<CachedResponse> {
+        let entry =
+            serde_json::from_str::<CacheEntry>(&read_to_string(self.entry_path(path)).ok()?)
+                .ok()?;
+        // hash collision, treat as a miss
+        if entry.path != path {
+            return None;
+        }
+        let body = read(self.body_path(path)).ok()?;
+        Some(CachedResponse { entry, body })
+    }
+
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        let max_age = self.policy.max_age(ResourceKind::from_path(&entry.path));
+        now().saturating_sub(entry.fetched) < max_age.as_secs()
+    }
+
+    pub fn store(
+        &self,
+        path: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        body: &[u8],
+    ) -> io::Result<()> {
+        // it would never be fresh, so it would only be refetched anyway
+        if self.policy.max_age(ResourceKind::from_path(path)).is_zero() {
+            return Ok(());
+        }
+        create_dir_all(&self.dir)?;
+        let entry = CacheEntry {
+            path: path.to_string(),
+            etag,
+            last_modified,
+            fetched: now(),
+        };
+        write(self.body_path(path), body)?;
+        write(self.entry_path(path), serde_json::to_string(&entry)?)?;
+        self.evict()
+    }
+
+    /// Removes the entries last fetched or revalidated longest ago until the cache fits in
+    /// `policy.max_size`. Files are dated by their modification time, which `store` and
+    /// `touch` update.
+    fn evict(&self) -> io::Result<()> {
+        if self.policy.max_size == 0 {
+            return Ok(());
+        }
+        // (size, last written) by key, an entry is its metadata and body files together
+        let mut entries = HashMap::<String, (u64, SystemTime)>::new();
+        for file in read_dir(&self.dir)? {
+            let file = file?;
+            let metadata = file.metadata()?;
+            let path = file.path();
+            let Some(key) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            let entry = entries.entry(key.to_string()).or_insert((0, UNIX_EPOCH));
+            entry.0 += metadata.len();
+            entry.1 = entry.1.max(modified);
+        }
+        let mut total = entries.values().map(|(size, _)| size).sum::<u64>();
+        if total <= self.policy.max_size {
+            return Ok(());
+        }
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(_, (_, modified))| *modified);
+        for (key, (size, _)) in entries {
+            if total <= self.policy.max_size {
+                break;
+            }
+            for extension in ["json", "body"] {
+                match remove_file(self.dir.join(format!("{}.{}", key, extension))) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// marks a cached entry as fetched now, after the server answered 304
+    pub fn touch(&self, mut entry: CacheEntry) -> io::Result<()> {
+        entry.fetched = now();
+        write(self.entry_path(&entry.path), serde_json::to_string(&entry)?)
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        match remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::iter::zip;
+
+    fn cache(name: &str, policy: CachePolicy) -> ResponseCache {
+        let dir = std::env::temp_dir().join(format!("royal_rust_{}_{}", name, std::process::id()));
+        let cache = ResponseCache::with_policy(dir, policy);
+        cache.clear().unwrap();
+        cache
+    }
+
+    fn entry(path: &str, age: u64) -> CacheEntry {
+        CacheEntry {
+            path: path.to_string(),
+            etag: None,
+            last_modified: None,
+            fetched: now() - age,
+        }
+    }
+
+    #[test]
+    fn resource_kind_from_path() {
+        let kind = ResourceKind::from_path;
+        assert_eq!(kind("/fiction/21220/mother-of-learning"), ResourceKind::Fiction);
+        assert_eq!(
+            kind("/fiction/21220/mother-of-learning/chapter/301778/1-good-morning-brother"),
+            ResourceKind::Chapter
+        );
+        assert_eq!(kind("https://www.royalroadcdn.com/covers/x.JPG"), ResourceKind::Image);
+        assert_eq!(kind("/covers/x.png?time=1#top"), ResourceKind::Image);
+        assert_eq!(kind("/fictions/search?title=a"), ResourceKind::Other);
+        assert_eq!(kind("/profile/1"), ResourceKind::Other);
+    }
+
+    #[test]
+    fn freshness_depends_on_the_kind() {
+        let cache = cache(
+            "fresh",
+            CachePolicy {
+                fiction_max_age: Duration::from_secs(60),
+                chapter_max_age: Duration::from_secs(3600),
+                image_max_age: Duration::ZERO,
+                other_max_age: Duration::from_secs(60),
+                max_size: 0,
+            },
+        );
+        assert!(cache.is_fresh(&entry("/fiction/1", 30)));
+        assert!(!cache.is_fresh(&entry("/fiction/1", 120)));
+        assert!(cache.is_fresh(&entry("/fiction/1/x/chapter/2/y", 120)));
+        assert!(!cache.is_fresh(&entry("/fiction/1/x/chapter/2/y", 7200)));
+        assert!(!cache.is_fresh(&entry("/covers/1.png", 0)));
+        assert!(!cache.is_fresh(&entry("/profile/1", 60)));
+    }
+
+    #[test]
+    fn kinds_with_no_max_age_are_not_stored() {
+        let cache = cache("skip", CachePolicy::default());
+        cache.store("/profile/1", None, None, b"profile").unwrap();
+        assert!(cache.lookup("/profile/1").is_none());
+        cache
+            .store("/fiction/1", Some(String::from("\"v1\"")), None, b"fiction")
+            .unwrap();
+        let cached = cache.lookup("/fiction/1").unwrap();
+        assert_eq!(cached.body, b"fiction");
+        assert_eq!(cached.entry.etag.as_deref(), Some("\"v1\""));
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn eviction_removes_the_least_recently_fetched_first() {
+        let cache = cache("evict", CachePolicy::default());
+        let paths = ["/fiction/1", "/fiction/2", "/fiction/3"];
+        for path in paths {
+            cache.store(path, None, None, &[0; 100]).unwrap();
+        }
+        // fetched in the order 2, 1, 3
+        let base = SystemTime::now() - Duration::from_secs(600);
+        for (path, age) in zip(paths, [200, 300, 100]) {
+            for file in [cache.entry_path(path), cache.body_path(path)] {
+                let file = File::options().write(true).open(file).unwrap();
+                file.set_modified(base - Duration::from_secs(age)).unwrap();
+            }
+        }
+        let total = read_dir(cache.dir())
+            .unwrap()
+            .map(|f| f.unwrap().metadata().unwrap().len())
+            .sum::<u64>();
+        let smaller = ResponseCache::with_policy(
+            cache.dir().to_path_buf(),
+            CachePolicy {
+                max_size: total - 1,
+                ..CachePolicy::default()
+            },
+        );
+        smaller.evict().unwrap();
+        assert!(cache.lookup("/fiction/1").is_some());
+        assert!(cache.lookup("/fiction/2").is_none());
+        assert!(cache.lookup("/fiction/3").is_some());
+        cache.clear().unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_to_string, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Chapter;
+
+/// characters of context kept on each side of a hit in its snippet
+const SNIPPET_CONTEXT: usize = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct IndexedChapter {
+    path: String,
+    title: String,
+    published: u64,
+    paragraphs: Vec<String>,
+}
+
+/// One paragraph containing every term of a query.
+#[derive(Debug, Clone)]
+pub struct IndexHit {
+    pub chapter_path: String,
+    pub chapter_title: String,
+    /// index into the chapter's `content`
+    pub paragraph: usize,
+    /// the paragraph cut down to the text around the first term
+    pub snippet: String,
+}
+
+/// Full-text index over the downloaded chapters of one fiction.
+/// The chapter text is what gets saved, the term lookup is rebuilt on load.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FictionIndex {
+    pub fiction: usize,
+    chapters: Vec<IndexedChapter>,
+    /// term to (chapter, paragraph) pairs, in order
+    #[serde(skip)]
+    postings: HashMap<String, Vec<(usize, usize)>>,
+}
+
+/// lowercased alphanumeric runs, so "Fire-Bolt!" is "fire" and "bolt"
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+impl FictionIndex {
+    pub fn new(fiction: usize) -> FictionIndex {
+        Self {
+            fiction,
+            ..Default::default()
+        }
+    }
+
+    pub fn default_dir() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("royal_rust").join("index"))
+    }
+
+    fn file(dir: &Path, fiction: usize) -> PathBuf {
+        dir.join(format!("{}.json", fiction))
+    }
+
+    /// Loads the index of `fiction` from `dir`, or an empty one if none was saved yet.
+    pub fn load(dir: &Path, fiction: usize) -> io::Result<FictionIndex> {
+        let text = match read_to_string(Self::file(dir, fiction)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new(fiction)),
+            Err(e) => return Err(e),
+        };
+        let mut index: FictionIndex = serde_json::from_str(&text)?;
+        for chapter in 0..index.chapters.len() {
+            index.index_chapter(chapter);
+        }
+        Ok(index)
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        create_dir_all(dir)?;
+        let mut file = File::create(Self::file(dir, self.fiction))?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())
+    }
+
+    pub fn len(&self) -> usize {
+        self.chapters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chapters.is_empty()
+    }
+
+    /// whether `chapter` is indexed as it reads now, an edited chapter is not
+    pub fn is_current(&self, chapter: &Chapter) -> bool {
+        self.chapters.iter().any(|c| {
+            c.path == chapter.path && c.title == chapter.name && c.paragraphs == chapter.content
+        })
+    }
+
+    /// Adds `chapter`, replacing an older copy with the same path.
+    pub fn add_chapter(&mut self, chapter: &Chapter) {
+        let indexed = IndexedChapter {
+            path: chapter.path.clone(),
+            title: chapter.name.clone(),
+            published: chapter.published,
+            paragraphs: chapter.content.clone(),
+        };
+        match self.chapters.iter().position(|c| c.path == chapter.path) {
+            Some(i) => {
+                self.chapters[i] = indexed;
+                self.postings.clear();
+                for chapter in 0..self.chapters.len() {
+                    self.index_chapter(chapter);
+                }
+            }
+            None => {
+                self.chapters.push(indexed);
+                self.index_chapter(self.chapters.len() - 1);
+            }
+        }
+    }
+
+    fn index_chapter(&mut self, chapter: usize) {
+        for (paragraph, text) in self.chapters[chapter].paragraphs.iter().enumerate() {
+            for term in tokenize(text) {
+                let postings = self.postings.entry(term).or_default();
+                if postings.last() != Some(&(chapter, paragraph)) {
+                    postings.push((chapter, paragraph));
+                }
+            }
+        }
+    }
+
+    /// Every paragraph containing all terms of `query`, in release order.
+    pub fn search(&self, query: &str) -> Vec<IndexHit> {
+        let terms = tokenize(query).collect::<Vec<_>>();
+        let Some((first, rest)) = terms.split_first() else {
+            return Vec::new();
+        };
+        let Some(candidates) = self.postings.get(first) else {
+            return Vec::new();
+        };
+        let mut hits = candidates
+            .iter()
+            .filter(|location| {
+                rest.iter().all(|term| {
+                    self.postings
+                        .get(term)
+                        .is_some_and(|p| p.binary_search(location).is_ok())
+                })
+            })
+            .map(|&(chapter, paragraph)| {
+                let indexed = &self.chapters[chapter];
+                (
+                    indexed.published,
+                    IndexHit {
+                        chapter_path: indexed.path.clone(),
+                        chapter_title: indexed.title.clone(),
+                        paragraph,
+                        snippet: snippet(&indexed.paragraphs[paragraph], first),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by_key(|(published, hit)| (*published, hit.paragraph));
+        hits.into_iter().map(|(_, hit)| hit).collect()
+    }
+}
+
+/// the chars of `chars` whose lowercase is `term`, as a start and end index
+fn find_lowercase(chars: &[char], term: &str) -> Option<(usize, usize)> {
+    (0..chars.len()).find_map(|start| {
+        let mut want = term.chars().peekable();
+        let mut end = start;
+        while want.peek().is_some() {
+            // a char can lowercase to several, like 'İ', so match on the original chars
+            for lower in chars.get(end)?.to_lowercase() {
+                if want.next() != Some(lower) {
+                    return None;
+                }
+            }
+            end += 1;
+        }
+        Some((start, end))
+    })
+}
+
+/// the text around the first occurrence of `term`, cut at char boundaries
+fn snippet(text: &str, term: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let (start, end) = find_lowercase(&chars, term).unwrap_or((0, 0));
+    let from = start.saturating_sub(SNIPPET_CONTEXT);
+    let to = (end + SNIPPET_CONTEXT).min(chars.len());
+    let mut snippet = chars[from..to].iter().collect::<String>();
+    if from > 0 {
+        snippet.insert(0, '…');
+    }
+    if to < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(path: &str, published: u64, content: &[&str]) -> Chapter {
+        Chapter {
+            name: path.trim_start_matches('/').to_string(),
+            path: path.to_string(),
+            content: content.iter().map(|p| p.to_string()).collect(),
+            published,
+            edited: 0,
+        }
+    }
+
+    fn found(index: &FictionIndex, query: &str) -> Vec<(String, usize)> {
+        index
+            .search(query)
+            .into_iter()
+            .map(|hit| (hit.chapter_path, hit.paragraph))
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_splits_on_anything_not_alphanumeric() {
+        assert_eq!(
+            tokenize("Fire-Bolt! It's 42ÉCLAIRS").collect::<Vec<_>>(),
+            ["fire", "bolt", "it", "s", "42éclairs"]
+        );
+        assert_eq!(tokenize(" -- ").count(), 0);
+    }
+
+    #[test]
+    fn hits_need_every_term_and_come_in_release_order() {
+        let mut index = FictionIndex::new(1);
+        index.add_chapter(&chapter("/two", 20, &["the red fox", "a fox, red and quick"]));
+        index.add_chapter(&chapter("/one", 10, &["Red. Fox.", "a fox"]));
+        assert_eq!(
+            found(&index, "fox RED"),
+            [
+                (String::from("/one"), 0),
+                (String::from("/two"), 0),
+                (String::from("/two"), 1),
+            ]
+        );
+        assert!(found(&index, "red wolf").is_empty());
+        assert!(found(&index, "").is_empty());
+    }
+
+    #[test]
+    fn an_edited_chapter_replaces_its_postings() {
+        let mut index = FictionIndex::new(1);
+        let old = chapter("/one", 10, &["the red fox"]);
+        index.add_chapter(&old);
+        assert!(index.is_current(&old));
+        let edited = chapter("/one", 10, &["the blue fox"]);
+        assert!(!index.is_current(&edited));
+        index.add_chapter(&edited);
+        assert!(found(&index, "red").is_empty());
+        assert_eq!(found(&index, "blue"), [(String::from("/one"), 0)]);
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn snippets_cut_multibyte_text_around_the_term() {
+        // each 'İ' lowercases to two chars, which must not shift the cut
+        let text = format!("{} needle {}", "İ".repeat(100), "é".repeat(100));
+        assert_eq!(
+            snippet(&text, "needle"),
+            format!("…{} needle {}…", "İ".repeat(59), "é".repeat(59))
+        );
+        assert_eq!(snippet("İİ", "missing"), "İİ");
+        assert_eq!(snippet("İstanbul", "i̇stanbul"), "İstanbul");
+    }
+}
@@ -1,30 +1,152 @@
-use chrono::NaiveDateTime;
-use reqwest::blocking::{Client, Response};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::{
+    blocking::{Client, Response},
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER},
+    StatusCode,
+};
 use select::{
     document::Document,
     node::{Data, Node},
-    predicate::{Child, Class, Name},
+    predicate::{Attr, Child, Class, Name, Predicate},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fmt;
 use std::fs::{create_dir_all, read_to_string, File};
+use std::hash::{BuildHasher, Hasher};
 use std::io::Write;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+mod cache;
+mod index;
+pub use cache::{CacheEntry, CachePolicy, CachedResponse, ResourceKind, ResponseCache};
+pub use index::{tokenize, FictionIndex, IndexHit};
+
+#[derive(Debug)]
+pub enum RoyalError {
+    /// the request never produced a response (connection, timeout, body read)
+    Request(reqwest::Error),
+    /// the server answered with a non-success status, after any retries
+    Status(StatusCode),
+    /// the page was fetched but did not look like what we expected
+    Parse(&'static str),
+}
+
+impl fmt::Display for RoyalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoyalError::Request(e) => write!(f, "request failed: {}", e),
+            RoyalError::Status(status) => write!(f, "server returned {}", status),
+            RoyalError::Parse(what) => write!(f, "failed to parse {}", what),
+        }
+    }
+}
+
+impl std::error::Error for RoyalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RoyalError::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for RoyalError {
+    fn from(value: reqwest::Error) -> Self {
+        RoyalError::Request(value)
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Fiction {
     pub title: String,
     pub id: usize,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub status: FictionStatus,
     pub chapters: Vec<ChapterReference>,
+    /// books or volumes the chapters are grouped into, in reading order; often none
+    #[serde(default)]
+    pub volumes: Vec<Volume>,
+}
+
+/// A book or volume of a fiction, which chapters refer to by `id`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Volume {
+    pub id: usize,
+    pub title: String,
+}
+
+/// Publication status, from the labels on a fiction's page.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FictionStatus {
+    #[default]
+    Unknown,
+    Ongoing,
+    Completed,
+    Hiatus,
+    Stub,
+    Dropped,
+}
+
+impl FictionStatus {
+    /// the status a page label like `ONGOING` stands for, if it is one
+    pub fn from_label(label: &str) -> Option<FictionStatus> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "ongoing" => Some(FictionStatus::Ongoing),
+            "completed" => Some(FictionStatus::Completed),
+            "hiatus" => Some(FictionStatus::Hiatus),
+            "stub" => Some(FictionStatus::Stub),
+            "dropped" => Some(FictionStatus::Dropped),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FictionStatus::Unknown => "unknown",
+            FictionStatus::Ongoing => "ongoing",
+            FictionStatus::Completed => "completed",
+            FictionStatus::Hiatus => "hiatus",
+            FictionStatus::Stub => "stub",
+            FictionStatus::Dropped => "dropped",
+        }
+    }
+}
+
+/// One entry of royalroad's title search.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SearchResult {
+    pub id: usize,
+    pub title: String,
+    pub author: Option<String>,
+    pub pages: Option<usize>,
+    pub rating: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ChapterReference {
+    #[serde(default)]
+    pub id: usize,
     pub path: String,
     pub title: String,
     pub time: u64,
+    /// premium or otherwise unavailable to an anonymous reader
+    #[serde(default)]
+    pub locked: bool,
+    /// id of the `Volume` it is part of
+    #[serde(default)]
+    pub volume: Option<usize>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Chapter {
     pub name: String,
     pub path: String,
@@ -33,8 +155,88 @@ pub struct Chapter {
     pub edited: u64,
 }
 
+/// A fiction, and optionally one of its chapters, as pointed to by user input.
+/// A `/fiction/chapter/<id>` link only names the chapter, see
+/// `RoyalClient::fiction_of_chapter`; at least one of the two is always set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FictionLink {
+    pub fiction: Option<usize>,
+    pub chapter: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkError(&'static str);
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl FromStr for FictionLink {
+    type Err = LinkError;
+
+    /// Accepts a bare id, a full royalroad url, or a `/fiction/<id>/<slug>` path,
+    /// with or without a trailing `/chapter/<id>/<slug>`, or a `/fiction/chapter/<id>`
+    /// short chapter link.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(LinkError("enter a fiction id or link"));
+        }
+        if let Ok(fiction) = s.parse::<usize>() {
+            return Ok(FictionLink {
+                fiction: Some(fiction),
+                chapter: None,
+            });
+        }
+        let s = s.split(['?', '#']).next().unwrap_or(s);
+        let s = s
+            .strip_prefix("https://")
+            .or_else(|| s.strip_prefix("http://"))
+            .unwrap_or(s);
+        let path = match s.split_once('/') {
+            Some((host, path)) if host.contains('.') => {
+                if !matches!(host, "royalroad.com" | "www.royalroad.com") {
+                    return Err(LinkError("not a royalroad link"));
+                }
+                path
+            }
+            _ => s.strip_prefix('/').unwrap_or(s),
+        };
+        let segments = path.split('/').collect::<Vec<_>>();
+        if segments.first() != Some(&"fiction") {
+            return Err(LinkError("link does not point to a fiction"));
+        }
+        let fiction = match segments.get(1) {
+            Some(&"chapter") => None,
+            x => Some(
+                x.and_then(|x| x.parse::<usize>().ok())
+                    .ok_or(LinkError("link is missing the fiction id"))?,
+            ),
+        };
+        let chapter = match segments.iter().position(|x| *x == "chapter") {
+            Some(i) => Some(
+                segments
+                    .get(i + 1)
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .ok_or(LinkError("link is missing the chapter id"))?,
+            ),
+            None => None,
+        };
+        Ok(FictionLink { fiction, chapter })
+    }
+}
+
 impl Fiction {
-    pub fn write_to_file(path: &Path, fictions: &Vec<Fiction>) -> std::io::Result<()> {
+    pub fn write_to_file(path: &Path, fictions: &[Fiction]) -> std::io::Result<()> {
+        Self::write_ids(path, &fictions.iter().map(|f| f.id).collect::<Vec<_>>())
+    }
+
+    /// Writes the library file, one fiction id per line.
+    pub fn write_ids(path: &Path, ids: &[usize]) -> std::io::Result<()> {
         if let Some(parent) = path.parent() {
             create_dir_all(parent)?;
         }
@@ -44,9 +246,8 @@ impl Fiction {
             .truncate(true)
             .open(path)?;
         file.write_all(
-            fictions
-                .iter()
-                .map(|f| f.id.to_string())
+            ids.iter()
+                .map(|id| id.to_string())
                 .collect::<Vec<_>>()
                 .join("\n")
                 .as_bytes(),
@@ -54,15 +255,16 @@ impl Fiction {
         Ok(())
     }
 
-    pub fn from_file(client: &RoyalClient, path: &Path) -> std::io::Result<Vec<Fiction>> {
+    /// The fiction ids in a library file, in the order they are listed.
+    pub fn read_ids(path: &Path) -> std::io::Result<Vec<usize>> {
         Ok(read_to_string(path)?
             .split('\n')
-            .filter_map(|s| client.get_fiction(s.parse::<usize>().ok()?))
+            .filter_map(|s| s.trim().parse::<usize>().ok())
             .collect())
     }
 }
 
-fn traverse<'a, 'b>(n: &'a Node, v: &'b Vec<usize>) -> Option<Node<'a>> {
+fn traverse<'a>(n: &'a Node, v: &[usize]) -> Option<Node<'a>> {
     let mut v = v.iter();
     let mut cur: Node = n.children().nth(*v.next()?)?;
     for i in v {
@@ -90,35 +292,90 @@ struct OfficialChapterReference {
     url: String,
 }
 
-impl From<OfficialChapterReference> for ChapterReference {
-    fn from(value: OfficialChapterReference) -> Self {
-        Self {
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+struct OfficialVolume {
+    id: usize,
+    title: String,
+    #[serde(default)]
+    order: usize,
+}
+
+/// The JSON array a page script assigns to `window.<name>`.
+/// Brackets inside strings, like in a chapter titled "[Side Story]", are skipped.
+fn script_array<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let assignment = format!("window.{} = ", name);
+    let rest = &text[text.find(&assignment)? + assignment.len()..];
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, byte) in rest.bytes().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' => depth += 1,
+            b']' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return (byte == b']').then(|| &rest[..=i]);
+        }
+    }
+    None
+}
+
+impl TryFrom<OfficialChapterReference> for ChapterReference {
+    type Error = RoyalError;
+    fn try_from(value: OfficialChapterReference) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
             path: value.url,
             title: value.title,
             time: NaiveDateTime::parse_from_str(&value.date, "%Y-%m-%dT%H:%M:%SZ")
-                .unwrap()
+                .map_err(|_| RoyalError::Parse("chapter date"))?
                 .and_utc()
                 .timestamp() as u64,
-        }
+            locked: !value.is_unlocked,
+            volume: value.volume_id,
+        })
     }
 }
 
 impl Chapter {
-    pub fn from_reference(reference: &ChapterReference, client: &RoyalClient) -> Option<Chapter> {
-        let result = client.get(&reference.path).ok()?;
-        let document = Document::from_read(result.text().ok()?.as_bytes()).ok()?;
-        let profile_info: Node = document.find(Class("profile-info")).next().unwrap();
-        let published = traverse(&profile_info, &vec![3, 1, 3])?
-            .attr("unixtime")?
-            .parse::<u64>()
-            .ok()?;
-        let edited = match traverse(&profile_info, &vec![3, 3, 3]) {
-            Some(x) => x.attr("unixtime")?.parse::<u64>().ok()?,
+    pub fn from_reference(
+        reference: &ChapterReference,
+        client: &RoyalClient,
+    ) -> Result<Chapter, RoyalError> {
+        let document = Document::from(client.get(&reference.path)?.as_str());
+        let profile_info: Node = document
+            .find(Class("profile-info"))
+            .next()
+            .ok_or(RoyalError::Parse("chapter header"))?;
+        let parse_time = |node: Node| {
+            node.attr("unixtime")
+                .and_then(|t| t.parse::<u64>().ok())
+                .ok_or(RoyalError::Parse("chapter timestamp"))
+        };
+        let published = parse_time(
+            traverse(&profile_info, &[3, 1, 3]).ok_or(RoyalError::Parse("chapter timestamp"))?,
+        )?;
+        let edited = match traverse(&profile_info, &[3, 3, 3]) {
+            Some(x) => parse_time(x)?,
             None => published,
         };
         let mut content = Vec::new();
         Self::join_content(
-            document.find(Class("chapter-content")).next()?,
+            document
+                .find(Class("chapter-content"))
+                .next()
+                .ok_or(RoyalError::Parse("chapter content"))?,
             &mut content,
         );
         let chapter = Chapter {
@@ -128,7 +385,7 @@ impl Chapter {
             published,
             edited,
         };
-        Some(chapter)
+        Ok(chapter)
     }
 
     fn join_content(node: Node, content: &mut Vec<String>) {
@@ -141,7 +398,7 @@ impl Chapter {
             Data::Text(..) => {
                 if node.text().as_str() == "\n"
                     && !content.is_empty()
-                    && content.last().unwrap().len() != 0
+                    && !content.last().unwrap().is_empty()
                 {
                     content.push(String::new());
                 } else if node.text() != "\n" {
@@ -157,71 +414,563 @@ impl Chapter {
     }
 }
 
+/// How `RoyalClient` paces, times out and retries its requests.
+#[derive(Debug, Clone)]
+pub struct RequestPolicy {
+    /// total time allowed for a single request, including reading the body
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// upper bound on requests started per second, 0 disables the limit
+    pub requests_per_second: f64,
+    /// retries after the first attempt for 429, 5xx and connection errors
+    pub max_retries: u32,
+    /// delay before the first retry, doubled on every further attempt
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    /// a `Retry-After` longer than this fails immediately instead of blocking
+    pub max_retry_after: Duration,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(20),
+            connect_timeout: Duration::from_secs(10),
+            requests_per_second: 2.0,
+            max_retries: 4,
+            backoff_base: Duration::from_millis(500),
+            backoff_max: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RequestPolicy {
+    fn min_interval(&self) -> Option<Duration> {
+        if self.requests_per_second > 0.0 {
+            Some(Duration::from_secs_f64(1.0 / self.requests_per_second))
+        } else {
+            None
+        }
+    }
+
+    /// exponential backoff with equal jitter: half the delay is fixed, half is random
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .backoff_base
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.backoff_max);
+        let random = RandomState::new().build_hasher().finish();
+        delay / 2 + delay.mul_f64((random % 1000) as f64 / 2000.0)
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// a request that never got an answer is worth retrying only if it might get one next time
+fn is_retryable_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout()
+}
+
+/// the site is unreachable or broken, rather than refusing this request
+fn is_outage(e: &RoyalError) -> bool {
+    match e {
+        RoyalError::Request(_) => true,
+        RoyalError::Status(status) => status.is_server_error(),
+        RoyalError::Parse(_) => false,
+    }
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, Utc::now())
+}
+
+/// reads `Retry-After` as either delta-seconds or an http date, a date in the past is now
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
 pub struct RoyalClient {
     client: Client,
+    policy: RequestPolicy,
+    next_request: Mutex<Option<Instant>>,
+    cache: Option<ResponseCache>,
+}
+
+impl Default for RoyalClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RoyalClient {
     pub fn new() -> RoyalClient {
+        Self::with_policy(RequestPolicy::default())
+    }
+
+    pub fn with_policy(policy: RequestPolicy) -> RoyalClient {
         RoyalClient {
-            client: Client::new(),
+            client: Client::builder()
+                .timeout(policy.timeout)
+                .connect_timeout(policy.connect_timeout)
+                .build()
+                .expect("failed to build http client"),
+            policy,
+            next_request: Mutex::new(None),
+            cache: None,
         }
     }
 
-    pub fn get_fiction(&self, id: usize) -> Option<Fiction> {
+    pub fn with_cache(mut self, cache: ResponseCache) -> RoyalClient {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn policy(&self) -> &RequestPolicy {
+        &self.policy
+    }
+
+    pub fn cache(&self) -> Option<&ResponseCache> {
+        self.cache.as_ref()
+    }
+
+    pub fn get_fiction(&self, id: usize) -> Result<Fiction, RoyalError> {
         let full_path = format!("/fiction/{}", id);
-        let result = self.get(&full_path).ok()?;
-        let document = Document::from_read(result.text().ok()?.as_bytes()).ok()?;
-        let title = document.find(Name("h1")).into_iter().next().unwrap().text();
+        let document = Document::from(self.get(&full_path)?.as_str());
+        let title = document
+            .find(Name("h1"))
+            .next()
+            .ok_or(RoyalError::Parse("fiction title"))?
+            .text();
+        let author = document
+            .find(Name("meta").and(Attr("property", "books:author")))
+            .next()
+            .and_then(|x| x.attr("content"))
+            .unwrap_or_default()
+            .to_string();
+        let description = document
+            .find(Class("description"))
+            .next()
+            .map(|x| x.text().trim().to_string())
+            .unwrap_or_default();
+        let status = document
+            .find(Name("span").and(Class("label")))
+            .find_map(|x| FictionStatus::from_label(&x.text()))
+            .unwrap_or_default();
 
         let possible_chap_lists = document
             .find(Child(Class("page-container-bg-solid"), Name("script")))
-            .into_iter()
             .collect::<Vec<_>>();
 
-        let text = possible_chap_lists[possible_chap_lists.len() - 3]
-            .children()
-            .next()
-            .unwrap()
+        let text = possible_chap_lists
+            .len()
+            .checked_sub(3)
+            .and_then(|i| possible_chap_lists[i].children().next())
+            .ok_or(RoyalError::Parse("fiction chapter list"))?
             .text();
 
-        let start_index = text
-            .find("window.chapters = ")
-            .expect("failed to find chapters")
-            + "window.chapters = ".len();
-        let skip_initial = &text.as_bytes()[start_index..];
-
-        let mut chapters_json = "";
-        let mut stack = 0;
-        for (i, byte) in skip_initial.iter().enumerate() {
-            if *byte == b'[' {
-                stack += 1;
-            } else if *byte == b']' {
-                stack -= 1;
-            }
-            if stack == 0 {
-                chapters_json = std::str::from_utf8(&skip_initial[0..=i])
-                    .expect("failed to convert from json to &str");
-                break;
-            }
-        }
-
+        let chapters_json =
+            script_array(&text, "chapters").ok_or(RoyalError::Parse("fiction chapter list"))?;
         let chapters = serde_json::from_str::<Vec<OfficialChapterReference>>(chapters_json)
-            .expect("failed to parse chapters json")
+            .map_err(|_| RoyalError::Parse("fiction chapter list"))?
+            .into_iter()
+            .map(ChapterReference::try_from)
+            .collect::<Result<Vec<ChapterReference>, RoyalError>>()?;
+        // volumes are optional, a fiction without them is just a flat list
+        let mut volumes = script_array(&text, "volumes")
+            .and_then(|json| serde_json::from_str::<Vec<OfficialVolume>>(json).ok())
+            .unwrap_or_default();
+        volumes.sort_by_key(|v| v.order);
+        let volumes = volumes
             .into_iter()
-            .map(ChapterReference::from)
-            .collect::<Vec<ChapterReference>>();
+            .map(|v| Volume {
+                id: v.id,
+                title: v.title,
+            })
+            .collect();
 
-        Some(Fiction {
+        Ok(Fiction {
             id,
             title,
+            author,
+            description,
+            status,
             chapters,
+            volumes,
         })
     }
 
-    pub fn get(&self, path: &str) -> Result<Response, reqwest::Error> {
-        self.client
-            .get(format!("https://royalroad.com{}", path))
-            .send()
+    /// The id of the fiction `chapter` is in, from where its short link redirects to.
+    pub fn fiction_of_chapter(&self, chapter: usize) -> Result<usize, RoyalError> {
+        let response = self.send(&format!("/fiction/chapter/{}", chapter), HeaderMap::new())?;
+        response
+            .url()
+            .path()
+            .parse::<FictionLink>()
+            .ok()
+            .and_then(|link| link.fiction)
+            .ok_or(RoyalError::Parse("chapter link redirect"))
+    }
+
+    /// Searches fictions by title, returning the first page of results.
+    pub fn search(&self, title: &str) -> Result<Vec<SearchResult>, RoyalError> {
+        let url = reqwest::Url::parse_with_params(
+            "https://royalroad.com/fictions/search",
+            &[("title", title)],
+        )
+        .map_err(|_| RoyalError::Parse("search query"))?;
+        let document = Document::from(self.get(url.as_str())?.as_str());
+        let number = |s: &str| {
+            s.split_whitespace()
+                .next()
+                .and_then(|x| x.replace(',', "").parse::<usize>().ok())
+        };
+        Ok(document
+            .find(Class("fiction-list-item"))
+            .filter_map(|item| {
+                let link = item
+                    .find(Class("fiction-title").descendant(Name("a")))
+                    .next()?;
+                let id = link.attr("href")?.parse::<FictionLink>().ok()?.fiction?;
+                let author = item
+                    .find(Name("a"))
+                    .find(|x| x.attr("href").is_some_and(|h| h.starts_with("/profile/")))
+                    .map(|x| x.text().trim().to_string());
+                let pages = item
+                    .find(Class("stats").descendant(Name("span")))
+                    .map(|x| x.text())
+                    .find(|x| x.trim().ends_with("Pages"))
+                    .and_then(|x| number(&x));
+                let rating = item
+                    .find(Class("star"))
+                    .next()
+                    .and_then(|x| x.attr("title"))
+                    .and_then(|x| x.trim().parse::<f32>().ok());
+                Some(SearchResult {
+                    id,
+                    title: link.text().trim().to_string(),
+                    author,
+                    pages,
+                    rating,
+                })
+            })
+            .collect())
+    }
+
+    /// Fetches the body of `path` as text, see `get_bytes`.
+    pub fn get(&self, path: &str) -> Result<String, RoyalError> {
+        String::from_utf8(self.get_bytes(path)?).map_err(|_| RoyalError::Parse("response body"))
+    }
+
+    /// Fetches the body of `path`, which is either under royalroad.com or a full url.
+    /// With a cache attached, fresh copies are served from disk and stale ones are
+    /// revalidated with a conditional request. If the site can't be reached or fails,
+    /// a stale copy is served rather than nothing.
+    pub fn get_bytes(&self, path: &str) -> Result<Vec<u8>, RoyalError> {
+        let Some(cache) = &self.cache else {
+            return Ok(self.send(path, HeaderMap::new())?.bytes()?.to_vec());
+        };
+        let cached = cache.lookup(path);
+        let mut headers = HeaderMap::new();
+        if let Some(cached) = &cached {
+            if cache.is_fresh(&cached.entry) {
+                return Ok(cached.body.clone());
+            }
+            if let Some(etag) = cached.entry.etag.as_ref().and_then(|x| x.parse().ok()) {
+                headers.insert(IF_NONE_MATCH, etag);
+            }
+            if let Some(modified) = cached
+                .entry
+                .last_modified
+                .as_ref()
+                .and_then(|x| x.parse().ok())
+            {
+                headers.insert(IF_MODIFIED_SINCE, modified);
+            }
+        }
+        let response = match self.send(path, headers) {
+            Ok(response) => response,
+            Err(e) => {
+                return match cached {
+                    Some(cached) if is_outage(&e) => Ok(cached.body),
+                    _ => Err(e),
+                }
+            }
+        };
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) = (response.status(), cached) {
+            let _ = cache.touch(cached.entry);
+            return Ok(cached.body);
+        }
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(String::from)
+        };
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        let body = response.bytes()?.to_vec();
+        let _ = cache.store(path, etag, last_modified, &body);
+        Ok(body)
+    }
+
+    /// Sends one GET according to the client's `RequestPolicy`. Only successful
+    /// (or 304) responses are returned, everything else becomes a `RoyalError`.
+    fn send(&self, path: &str, headers: HeaderMap) -> Result<Response, RoyalError> {
+        let url = if path.starts_with("http") {
+            path.to_string()
+        } else {
+            format!("https://royalroad.com{}", path)
+        };
+        let mut attempt = 0;
+        loop {
+            self.throttle();
+            let delay = match self.client.get(&url).headers(headers.clone()).send() {
+                Ok(response)
+                    if response.status().is_success()
+                        || response.status() == StatusCode::NOT_MODIFIED =>
+                {
+                    return Ok(response)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    if !is_retryable(status) || attempt >= self.policy.max_retries {
+                        return Err(RoyalError::Status(status));
+                    }
+                    match retry_after(&response) {
+                        Some(x) if x > self.policy.max_retry_after => {
+                            return Err(RoyalError::Status(status))
+                        }
+                        Some(x) => x,
+                        None => self.policy.backoff(attempt),
+                    }
+                }
+                Err(e) => {
+                    if !is_retryable_error(&e) || attempt >= self.policy.max_retries {
+                        return Err(e.into());
+                    }
+                    self.policy.backoff(attempt)
+                }
+            };
+            sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    /// reserves the next slot the rate limit allows, then blocks until it comes
+    fn throttle(&self) {
+        let Some(interval) = self.policy.min_interval() else {
+            return;
+        };
+        // the slot is reserved under the lock, the wait for it happens after releasing it
+        // so other threads can queue up behind instead of blocking on the mutex
+        let wait = {
+            let mut next_request = self.next_request.lock().unwrap();
+            let now = Instant::now();
+            let start = next_request.map_or(now, |next| next.max(now));
+            *next_request = Some(start + interval);
+            start - now
+        };
+        sleep(wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn library_file_keeps_ids_in_order() {
+        let path = std::env::temp_dir().join(format!("royal_rust_ids_{}", std::process::id()));
+        Fiction::write_ids(&path, &[21220, 7, 300]).unwrap();
+        assert_eq!(Fiction::read_ids(&path).unwrap(), [21220, 7, 300]);
+        std::fs::write(&path, "21220\n\nnot an id\n 7 \n").unwrap();
+        assert_eq!(Fiction::read_ids(&path).unwrap(), [21220, 7]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn backoff_doubles_within_jitter_bounds_up_to_the_max() {
+        let policy = RequestPolicy {
+            backoff_base: Duration::from_millis(100),
+            backoff_max: Duration::from_millis(1000),
+            ..RequestPolicy::default()
+        };
+        for (attempt, full) in [
+            (0, 100),
+            (1, 200),
+            (2, 400),
+            (3, 800),
+            (4, 1000),
+            (40, 1000),
+        ] {
+            let full = Duration::from_millis(full);
+            for _ in 0..50 {
+                let delay = policy.backoff(attempt);
+                assert!(
+                    delay >= full / 2 && delay <= full,
+                    "{:?} at {}",
+                    delay,
+                    attempt
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn retry_after_reads_seconds_and_dates() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:29:30 GMT", now),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("-5", now), None);
+    }
+
+    #[test]
+    fn only_rate_limits_server_errors_and_lost_connections_are_retried() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+        assert!(!is_retryable(StatusCode::FORBIDDEN));
+        // nothing listens on port 1, the connection is refused right away
+        let refused = Client::new().get("http://127.0.0.1:1/").send().unwrap_err();
+        assert!(is_retryable_error(&refused));
+        let invalid = Client::new().get("http://[invalid/").send().unwrap_err();
+        assert!(!is_retryable_error(&invalid));
+    }
+
+    #[test]
+    fn stale_copies_are_served_when_the_site_is_unreachable() {
+        let dir = std::env::temp_dir().join(format!("royal_rust_stale_{}", std::process::id()));
+        // stored, but never fresh
+        let cache = ResponseCache::with_policy(
+            dir.clone(),
+            CachePolicy {
+                chapter_max_age: Duration::from_millis(500),
+                ..CachePolicy::default()
+            },
+        );
+        let path = "http://127.0.0.1:1/fiction/1/x/chapter/2/y";
+        cache.store(path, None, None, b"chapter").unwrap();
+        let client = RoyalClient::with_policy(RequestPolicy {
+            max_retries: 0,
+            ..RequestPolicy::default()
+        })
+        .with_cache(cache);
+        assert_eq!(client.get_bytes(path).unwrap(), b"chapter");
+        assert!(client.get_bytes("http://127.0.0.1:1/fiction/1/x/chapter/3/z").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn link(fiction: Option<usize>, chapter: Option<usize>) -> Result<FictionLink, LinkError> {
+        Ok(FictionLink { fiction, chapter })
+    }
+
+    #[test]
+    fn fiction_link_accepts_every_shape() {
+        let fiction = link(Some(21220), None);
+        let chapter = link(Some(21220), Some(301778));
+        assert_eq!(" 21220 ".parse(), fiction);
+        assert_eq!("/fiction/21220".parse(), fiction);
+        assert_eq!("fiction/21220/mother-of-learning".parse(), fiction);
+        assert_eq!(
+            "https://www.royalroad.com/fiction/21220/mother-of-learning?tab=chapters".parse(),
+            fiction
+        );
+        assert_eq!("royalroad.com/fiction/21220/".parse(), fiction);
+        assert_eq!(
+            "http://royalroad.com/fiction/21220/mother-of-learning/chapter/301778/1-good-morning#c"
+                .parse(),
+            chapter
+        );
+        assert_eq!(
+            "/fiction/21220/mother-of-learning/chapter/301778/1-good-morning".parse(),
+            chapter
+        );
+        let short = link(None, Some(301778));
+        assert_eq!(
+            "https://www.royalroad.com/fiction/chapter/301778".parse(),
+            short
+        );
+        assert_eq!("/fiction/chapter/301778".parse(), short);
+    }
+
+    #[test]
+    fn fiction_link_rejects_other_links() {
+        let error = |s: &str| s.parse::<FictionLink>().unwrap_err().0;
+        assert_eq!(error("  "), "enter a fiction id or link");
+        assert_eq!(
+            error("https://example.com/fiction/1"),
+            "not a royalroad link"
+        );
+        assert_eq!(error("/profile/1"), "link does not point to a fiction");
+        assert_eq!(error("/fiction/abc"), "link is missing the fiction id");
+        assert_eq!(error("/fiction/chapter/"), "link is missing the chapter id");
+        assert_eq!(
+            error("/fiction/1/x/chapter/y"),
+            "link is missing the chapter id"
+        );
+    }
+
+    const CHAPTER: &str = r#"{"id":1,"title":"[Side Story] Brackets ] and \"quotes [","slug":"s","date":"2024-01-02T03:04:05Z","order":0,"visible":1,"volumeId":7,"subscriptionTiers":null,"doesNotRollOver":false,"isUnlocked":true,"url":"/fiction/1/f/chapter/1/s"}"#;
+
+    fn page(volumes: Option<&str>) -> String {
+        let mut page = format!(
+            "<script>\n    window.chapters = [{}];\n    window.chapterCount = 1;\n",
+            CHAPTER
+        );
+        if let Some(volumes) = volumes {
+            page.push_str(&format!("    window.volumes = {};\n", volumes));
+        }
+        page.push_str("</script>");
+        page
+    }
+
+    #[test]
+    fn script_array_skips_brackets_in_titles() {
+        let page = page(Some(r#"[{"id":7,"title":"Volume [1]","order":0}]"#));
+        let chapters = script_array(&page, "chapters").unwrap();
+        assert_eq!(chapters, format!("[{}]", CHAPTER));
+        let chapters = serde_json::from_str::<Vec<OfficialChapterReference>>(chapters).unwrap();
+        let chapter = ChapterReference::try_from(chapters.into_iter().next().unwrap()).unwrap();
+        assert_eq!(chapter.title, "[Side Story] Brackets ] and \"quotes [");
+        assert_eq!(chapter.volume, Some(7));
+        let volumes = script_array(&page, "volumes").unwrap();
+        let volumes = serde_json::from_str::<Vec<OfficialVolume>>(volumes).unwrap();
+        assert_eq!(volumes[0].title, "Volume [1]");
+    }
+
+    #[test]
+    fn script_array_is_none_when_missing_or_unclosed() {
+        let page = page(None);
+        assert!(script_array(&page, "chapters").is_some());
+        assert_eq!(script_array(&page, "volumes"), None);
+        assert_eq!(script_array("window.volumes = [[1, 2]", "volumes"), None);
+        assert_eq!(script_array("window.volumes = null;", "volumes"), None);
     }
 }
@@ -1,7 +1,9 @@
 mod app;
 
-fn main() -> std::io::Result<()> {
-    let mut app = app::App::new()?;
-    app.run()?;
-    Ok(())
+fn main() {
+    let result = app::App::new().and_then(|mut app| app.run());
+    if let Err(e) = result {
+        eprintln!("rrtui: {}", e);
+        std::process::exit(1);
+    }
 }